@@ -0,0 +1,302 @@
+//! Compact binary encoding, implemented on the enums with the `codec` option.
+//!
+//! A variant is written as the varint tag, which is the index of the variant, followed by its
+//! fields in the declaration order. The flat enum has the single tag for the flat variant, while
+//! the structured enum has one tag for each nesting level.
+//!
+//! Unsigned integers are written as LEB128 varints, and signed integers are zigzag-encoded
+//! first. Strings and `Vec`s are prefixed with their lengths, and arrays are written without
+//! the length. Since the items of zero-sized types take no input, their `Vec`s are decoded only
+//! up to [`MAX_ZERO_SIZED_LEN`] items.
+
+use core::fmt;
+
+/// The maximum length of `Vec<T>` decoded when `T` is zero-sized. Longer lengths are rejected
+/// with [`DecodeError::OutOfRange`], so that the length read from the input does not make the
+/// decoder loop without consuming the input.
+pub const MAX_ZERO_SIZED_LEN: usize = 1 << 16;
+
+/// Error returned from [`Decode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DecodeError {
+    /// The input ended in the middle of the value.
+    UnexpectedEnd,
+    /// The tag does not correspond to any variant.
+    InvalidTag(u64),
+    /// The value is out of the range of the type, like `300` for `u8`.
+    OutOfRange,
+    /// The string is not UTF-8.
+    InvalidUtf8,
+    /// The input has bytes after the value, returned from [`Decode::from_bytes()`].
+    TrailingBytes,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEnd => write!(f, "unexpected end of input"),
+            Self::InvalidTag(tag) => write!(f, "invalid tag {}", tag),
+            Self::OutOfRange => write!(f, "value out of range"),
+            Self::InvalidUtf8 => write!(f, "invalid UTF-8"),
+            Self::TrailingBytes => write!(f, "trailing bytes after the value"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Types which can be written in the binary encoding.
+pub trait Encode {
+    /// Appends the encoded value to `out`.
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Returns the encoded value.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+}
+
+/// Types which can be read from the binary encoding.
+pub trait Decode: Sized {
+    /// Reads the value from the beginning of `input`, and advances `input` past it.
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError>;
+
+    /// Reads the value which occupies the whole `bytes`.
+    fn from_bytes(mut bytes: &[u8]) -> Result<Self, DecodeError> {
+        let value = Self::decode(&mut bytes)?;
+        if bytes.is_empty() {
+            Ok(value)
+        } else {
+            Err(DecodeError::TrailingBytes)
+        }
+    }
+}
+
+/// Writes `value` as the LEB128 varint.
+pub fn write_varint(out: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads the LEB128 varint.
+pub fn read_varint(input: &mut &[u8]) -> Result<u128, DecodeError> {
+    let mut value = 0u128;
+    for shift in (0..128).step_by(7) {
+        let (&byte, rest) = input.split_first().ok_or(DecodeError::UnexpectedEnd)?;
+        *input = rest;
+        let bits = (byte & 0x7f) as u128;
+        if bits << shift >> shift != bits {
+            return Err(DecodeError::OutOfRange);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DecodeError::OutOfRange)
+}
+
+/// Writes the tag of the variant.
+pub fn write_tag(out: &mut Vec<u8>, tag: u64) {
+    write_varint(out, tag as u128)
+}
+
+/// Reads the tag of the variant.
+pub fn read_tag(input: &mut &[u8]) -> Result<u64, DecodeError> {
+    u64::try_from(read_varint(input)?).map_err(|_| DecodeError::OutOfRange)
+}
+
+fn read_bytes<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+    if input.len() < len {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+macro_rules! impl_unsigned {
+    ($($t:ty),*) => {$(
+        impl Encode for $t {
+            fn encode(&self, out: &mut Vec<u8>) {
+                write_varint(out, *self as u128)
+            }
+        }
+
+        impl Decode for $t {
+            fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                <$t>::try_from(read_varint(input)?).map_err(|_| DecodeError::OutOfRange)
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_signed {
+    ($($t:ty),*) => {$(
+        impl Encode for $t {
+            fn encode(&self, out: &mut Vec<u8>) {
+                let value = *self as i128;
+                write_varint(out, ((value << 1) ^ (value >> 127)) as u128)
+            }
+        }
+
+        impl Decode for $t {
+            fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                let value = read_varint(input)?;
+                let value = (value >> 1) as i128 ^ -((value & 1) as i128);
+                <$t>::try_from(value).map_err(|_| DecodeError::OutOfRange)
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_float {
+    ($($t:ty),*) => {$(
+        impl Encode for $t {
+            fn encode(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes())
+            }
+        }
+
+        impl Decode for $t {
+            fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                let bytes = read_bytes(input, core::mem::size_of::<$t>())?;
+                Ok(<$t>::from_le_bytes(bytes.try_into().unwrap()))
+            }
+        }
+    )*};
+}
+
+impl_unsigned!(u8, u16, u32, u64, u128, usize);
+impl_signed!(i8, i16, i32, i64, i128, isize);
+impl_float!(f32, f64);
+
+impl Encode for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8)
+    }
+}
+
+impl Decode for bool {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        match read_bytes(input, 1)?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::OutOfRange),
+        }
+    }
+}
+
+impl Encode for char {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u32).encode(out)
+    }
+}
+
+impl Decode for char {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        char::from_u32(u32::decode(input)?).ok_or(DecodeError::OutOfRange)
+    }
+}
+
+impl Encode for () {
+    fn encode(&self, _: &mut Vec<u8>) {}
+}
+
+impl Decode for () {
+    fn decode(_: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok(())
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        out.extend_from_slice(self.as_bytes())
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_str().encode(out)
+    }
+}
+
+impl Decode for String {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let len = usize::decode(input)?;
+        let bytes = read_bytes(input, len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_slice().encode(out)
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let len = usize::decode(input)?;
+        if core::mem::size_of::<T>() == 0 && len > MAX_ZERO_SIZED_LEN {
+            return Err(DecodeError::OutOfRange);
+        }
+        // Each item occupies at least one byte, except zero-sized ones.
+        let mut ret = Vec::with_capacity(len.min(input.len()));
+        for _ in 0..len {
+            ret.push(T::decode(input)?);
+        }
+        Ok(ret)
+    }
+}
+
+impl<T: Encode, const N: usize> Encode for [T; N] {
+    fn encode(&self, out: &mut Vec<u8>) {
+        for item in self {
+            item.encode(out);
+        }
+    }
+}
+
+impl<T: Decode, const N: usize> Decode for [T; N] {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let mut items = Vec::with_capacity(N);
+        for _ in 0..N {
+            items.push(T::decode(input)?);
+        }
+        Ok(items.try_into().ok().unwrap())
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (**self).encode(out)
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for Box<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (**self).encode(out)
+    }
+}
+
+impl<T: Decode> Decode for Box<T> {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        T::decode(input).map(Box::new)
+    }
+}
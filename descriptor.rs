@@ -0,0 +1,274 @@
+/// Static description of an enum, available as [`Describe::DESCRIPTOR`].
+///
+/// Variants declared with `#[flatten]` refer to the descriptor of the flattened enum, thus the
+/// descriptor forms a tree. The variants of the flat enum are the leaves of the tree, which can
+/// be listed with [`EnumDescriptor::flat_variants()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EnumDescriptor {
+    /// Name of the enum.
+    pub name: &'static str,
+    /// Path of the enum, starting with the crate name.
+    pub path: &'static str,
+    /// How the enum is defined.
+    pub kind: EnumKind,
+    /// Variants in the declaration order. On flat enums, these are the variants of the
+    /// structured enum.
+    pub variants: &'static [VariantDescriptor],
+}
+
+/// The macro which the enum is defined with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum EnumKind {
+    /// `#[derive(FlatTarget)]`
+    FlatTarget,
+    /// [`into_flat`](crate::into_flat)
+    Structured,
+    /// [`flat`](crate::flat)
+    Flat,
+}
+
+/// Static description of a variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VariantDescriptor {
+    /// Name of the variant.
+    pub name: &'static str,
+    /// The form of the fields.
+    pub kind: FieldsKind,
+    /// Fields in the declaration order.
+    pub fields: &'static [FieldDescriptor],
+    /// Descriptor of the field type, if the variant is declared with `#[flatten]`.
+    pub flatten: Option<&'static EnumDescriptor>,
+    /// The stable ID given with `#[flat_enum(id = N)]`, or `id_base` given with
+    /// `#[flatten(id_base = N)]`.
+    pub id: Option<u32>,
+}
+
+/// The form of the fields of a variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FieldsKind {
+    /// `E`
+    Unit,
+    /// `E(A, B)`
+    Unnamed,
+    /// `E { a: A, b: B }`
+    Named,
+}
+
+/// Static description of a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FieldDescriptor {
+    /// Name of the field, which is `None` on unnamed fields.
+    pub name: Option<&'static str>,
+    /// The type of the field, as written in the declaration.
+    pub ty: &'static str,
+}
+
+impl EnumDescriptor {
+    /// Returns the variants of the flat enum, which are the non-`#[flatten]` variants found in
+    /// the tree, in the declaration order.
+    pub fn flat_variants(&'static self) -> Vec<&'static VariantDescriptor> {
+        fn walk(desc: &'static EnumDescriptor, out: &mut Vec<&'static VariantDescriptor>) {
+            for variant in desc.variants {
+                match variant.flatten {
+                    Some(inner) => walk(inner, out),
+                    None => out.push(variant),
+                }
+            }
+        }
+        let mut ret = Vec::new();
+        walk(self, &mut ret);
+        ret
+    }
+
+    /// Returns the fingerprint of the variants, which is available as [`Describe::SCHEMA_HASH`].
+    ///
+    /// It is the FNV-1a hash of the variant names, the forms of the fields, the field names and
+    /// the field types as written, including the enums flattened with `#[flatten]`. The name and
+    /// the path of the enum itself are not included, thus the flat enum has the same hash as the
+    /// structured enum.
+    pub const fn schema_hash(&self) -> u64 {
+        hash_enum(FNV_OFFSET, self)
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+const fn hash_byte(hash: u64, byte: u8) -> u64 {
+    (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+}
+
+/// Hashes `s` followed by the terminator, which separates the adjacent strings.
+const fn hash_str(mut hash: u64, s: &str) -> u64 {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        hash = hash_byte(hash, bytes[i]);
+        i += 1;
+    }
+    hash_byte(hash, 0xff)
+}
+
+const fn hash_enum(mut hash: u64, desc: &EnumDescriptor) -> u64 {
+    hash = hash_byte(hash, b'{');
+    let mut i = 0;
+    while i < desc.variants.len() {
+        let variant = &desc.variants[i];
+        hash = hash_str(hash, variant.name);
+        hash = hash_byte(
+            hash,
+            match variant.kind {
+                FieldsKind::Unit => b'u',
+                FieldsKind::Unnamed => b't',
+                FieldsKind::Named => b'n',
+            },
+        );
+        let mut j = 0;
+        while j < variant.fields.len() {
+            let field = &variant.fields[j];
+            if let Some(name) = field.name {
+                hash = hash_str(hash, name);
+            }
+            hash = hash_str(hash, field.ty);
+            j += 1;
+        }
+        if let Some(inner) = variant.flatten {
+            hash = hash_enum(hash, inner);
+        }
+        hash = hash_byte(hash, b';');
+        i += 1;
+    }
+    hash_byte(hash, b'}')
+}
+
+/// Description of a flat enum, returned from [`describe()`]. Unlike [`EnumDescriptor`], the
+/// tree is resolved into the variants of the flat enum.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FlatDescription {
+    /// Name of the enum.
+    pub name: &'static str,
+    /// Path of the enum, starting with the crate name.
+    pub path: &'static str,
+    /// How the enum is defined.
+    pub kind: EnumKind,
+    /// The fingerprint of the variants, see [`Describe::SCHEMA_HASH`].
+    pub schema_hash: u64,
+    /// Variants of the flat enum, in the order of [`VariantInfo::variant_index()`].
+    ///
+    /// [`VariantInfo::variant_index()`]: crate::VariantInfo::variant_index
+    pub variants: Vec<FlatVariantDescription>,
+}
+
+/// Description of a variant of the flat enum.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FlatVariantDescription {
+    /// Name of the variant.
+    pub name: &'static str,
+    /// Names of `#[flatten]` variants which the variant comes through, like
+    /// [`VariantInfo::origin_path()`] without the last element.
+    ///
+    /// [`VariantInfo::origin_path()`]: crate::VariantInfo::origin_path
+    pub groups: Vec<&'static str>,
+    /// Path of the enum which declares the variant.
+    pub defined_in: &'static str,
+    /// The form of the fields.
+    pub kind: FieldsKind,
+    /// Fields in the declaration order, with the types as written in `defined_in`.
+    pub fields: &'static [FieldDescriptor],
+    /// The stable ID, which is the sum of the ID and `id_base` of the groups.
+    pub id: Option<u32>,
+}
+
+/// Returns the description of `T`, built from [`Describe::DESCRIPTOR`]. It lists the variants of
+/// the flat enum with the groups they come from, the field types and the stable IDs, which can
+/// be serialized with the `serde` feature for documentation or code generation.
+///
+/// ```
+/// # use flat_enum::{flat, into_flat, FlatTarget};
+/// #[derive(FlatTarget)]
+/// pub enum Enum1 {
+///     #[flat_enum(id = 1)]
+///     E1(u8),
+/// }
+///
+/// #[into_flat(Enum2Flat)]
+/// pub enum Enum2 {
+///     #[flatten(id_base = 100)]
+///     Enum1(Enum1),
+///     #[flat_enum(id = 1)]
+///     E2 { s: String },
+/// }
+///
+/// #[flat(Enum2)]
+/// pub enum Enum2Flat {}
+///
+/// let desc = flat_enum::describe::<Enum2Flat>();
+/// assert_eq!(desc.variants[0].groups, &["Enum1"]);
+/// assert_eq!(desc.variants[0].fields[0].ty, "u8");
+/// assert_eq!(desc.variants[0].id, Some(101));
+/// assert_eq!(desc.variants[1].name, "E2");
+/// ```
+pub fn describe<T: Describe + ?Sized>() -> FlatDescription {
+    // The macros reject the overflowing IDs, so it only fails on the hand-written descriptors.
+    fn add_id(base: u32, id: u32) -> u32 {
+        base.checked_add(id).expect("The stable ID overflows u32")
+    }
+
+    fn walk(
+        desc: &'static EnumDescriptor,
+        groups: &mut Vec<&'static str>,
+        id_base: Option<u32>,
+        out: &mut Vec<FlatVariantDescription>,
+    ) {
+        for variant in desc.variants {
+            match variant.flatten {
+                Some(inner) => {
+                    groups.push(variant.name);
+                    let base = match (id_base, variant.id) {
+                        (None, None) => None,
+                        (a, b) => Some(add_id(a.unwrap_or(0), b.unwrap_or(0))),
+                    };
+                    walk(inner, groups, base, out);
+                    groups.pop();
+                }
+                None => out.push(FlatVariantDescription {
+                    name: variant.name,
+                    groups: groups.clone(),
+                    defined_in: desc.path,
+                    kind: variant.kind,
+                    fields: variant.fields,
+                    id: variant.id.map(|id| add_id(id_base.unwrap_or(0), id)),
+                }),
+            }
+        }
+    }
+    let desc = T::DESCRIPTOR;
+    let mut variants = Vec::new();
+    walk(desc, &mut Vec::new(), None, &mut variants);
+    FlatDescription {
+        name: desc.name,
+        path: desc.path,
+        kind: desc.kind,
+        schema_hash: T::SCHEMA_HASH,
+        variants,
+    }
+}
+
+/// Provides [`EnumDescriptor`], implemented with `#[derive(FlatTarget)]`,
+/// [`into_flat`](crate::into_flat) and [`flat`](crate::flat).
+pub trait Describe {
+    /// The description of the enum.
+    const DESCRIPTOR: &'static EnumDescriptor;
+
+    /// The fingerprint of the variants, computed with [`EnumDescriptor::schema_hash()`]. It can
+    /// be pinned in a test or stored with the data, to detect the changes of the enums which
+    /// may live in other crates.
+    const SCHEMA_HASH: u64 = Self::DESCRIPTOR.schema_hash();
+}
@@ -0,0 +1,63 @@
+//! Support of `#[flat(.., ffi)]`, which exports the flat enum to C as the tag and the payload
+//! union.
+
+/// Field types which can be passed across FFI in the flat enum.
+///
+/// # Safety
+///
+/// The type should have the same layout as the C declaration given by [`FfiSafe::c_decl()`],
+/// and every bit pattern, including zero, should be a valid value.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be passed across FFI in the flat enum",
+    label = "unsupported field type",
+    note = "integers, floats, raw pointers and arrays of them are supported"
+)]
+pub unsafe trait FfiSafe: Copy {
+    /// Returns the C declaration of the field named `name`, like `uint8_t name`.
+    fn c_decl(name: &str) -> String;
+}
+
+macro_rules! impl_ffi_safe {
+    ($($t:ty => $c:literal),* $(,)?) => {$(
+        unsafe impl FfiSafe for $t {
+            fn c_decl(name: &str) -> String {
+                format!(concat!($c, " {}"), name)
+            }
+        }
+    )*};
+}
+
+impl_ffi_safe!(
+    u8 => "uint8_t",
+    u16 => "uint16_t",
+    u32 => "uint32_t",
+    u64 => "uint64_t",
+    usize => "uintptr_t",
+    i8 => "int8_t",
+    i16 => "int16_t",
+    i32 => "int32_t",
+    i64 => "int64_t",
+    isize => "intptr_t",
+    f32 => "float",
+    f64 => "double",
+);
+
+unsafe impl<T: FfiSafe, const N: usize> FfiSafe for [T; N] {
+    fn c_decl(name: &str) -> String {
+        T::c_decl(&format!("{}[{}]", name, N))
+    }
+}
+
+// The qualifier is written after the type specifier or after `*` of the pointee, like
+// `uint8_t const (*p)` or `uint8_t (*const (*p))`, so that it applies to the pointee at any depth.
+unsafe impl<T: FfiSafe> FfiSafe for *const T {
+    fn c_decl(name: &str) -> String {
+        T::c_decl(&format!("const (*{})", name))
+    }
+}
+
+unsafe impl<T: FfiSafe> FfiSafe for *mut T {
+    fn c_decl(name: &str) -> String {
+        T::c_decl(&format!("(*{})", name))
+    }
+}
@@ -25,11 +25,91 @@
 /// ## Arguments
 ///
 /// Specify the coresponding structured enum (which has [`IntoFlat`] trait implementation)
-/// as the first arguments.
-/// The flat enum should be exist in the same context (same module, function, or block context).
+/// as the first arguments. The path may have several segments like `inner::Enum2<A>`; the
+/// variants are given by the helper macro found by the same path.
 ///
-/// You can specify the `flat_enum` crate using atmark syntax like
-/// `#[flat(Enum2<A> @ ::flat_enum)]`.
+/// The path of the `flat_enum` crate is taken from the structured enum, so it needs not to be
+/// repeated. You can still specify it with `#[flat_enum(crate = ::flat_enum)]` on the enum, or
+/// using atmark syntax like `#[flat(Enum2<A> @ ::flat_enum)]`.
+///
+/// ## Options
+///
+/// Options follow the structured enum, separated with commas.
+///
+/// - `accessors`: generates `is_*`, `as_*`, `as_*_mut` and `into_*` methods for each variant.
+///   `as_*` returns the references to the fields as a tuple, and `into_*` returns the fields or
+///   gives back `self` as an error.
+/// - `debug = "structured"`: implements [`Debug`](core::fmt::Debug) which prints the value
+///   as the structured enum, like `Enum2::Enum1(Enum1::E1(5))` instead of `E1(5)`. The value is
+///   not converted; the structure is taken from [`Describe`] and [`VariantInfo::origin_path()`].
+/// - `structured(PartialEq, Eq, PartialOrd, Ord, Hash)`: implements the listed traits, which
+///   agree with the traits derived on the structured enum. The values are ordered and hashed by
+///   the variants of the nested enums which they come through, and then by the fields. The
+///   flattened enums should derive the traits, or be the flat enums with `structured(..)`.
+///   `PartialEq` also implements the comparison between the flat enum and the structured enum in
+///   both directions. `PartialOrd`, `Ord` and `Hash` are rejected if any of the enums has
+///   explicit discriminants or `#[repr(..)]`, since the derived impls then use the discriminant
+///   values. The impls name the variants of the flattened enums, so the flattened enums should be
+///   in scope where the flat enum is defined.
+/// - `visitor`: generates the visitor trait and `accept()` like `#[flat_enum(visitor)]` on
+///   [`FlatTarget`]. The visitor of the flat enum has the visitors of the flattened enums as
+///   supertraits, and only declares the methods for the other variants, so the existing visitors
+///   of the inner enums are reused. The flattened enums should also have the visitors, and the
+///   visitor traits like `Enum1Visitor` should be in scope where the flat enum is defined.
+/// - `map(A)`: generates `map()` and `to_ref()` which map the type parameter `A` in the fields,
+///   like `map(self, f: impl FnMut(A) -> B) -> Enum2Flat<B>` and
+///   `to_ref(&self) -> Enum2Flat<&A>`. Fields should have the type `A`, `Vec<A>`, `Option<A>`,
+///   `[A; N]`, or the enum with `map(A)`, or not contain `A`; the other types such as `Box<A>`
+///   are rejected. The fields without `A` are moved in `map()` and cloned in `to_ref()`, which is
+///   why it is not named `as_ref()`. It is also available in `#[into_flat]` and
+///   `#[flat_enum(..)]` on [`FlatTarget`], and the results agree with the structured enum
+///   through [`IntoFlat::into_flat()`].
+/// - `delegate(Trait, ..)`: implements the traits by forwarding each method to the payload,
+///   with a single `match` on the flat enum. The traits should be defined with
+///   [`delegatable`], and every variant should have exactly one field.
+/// - `common(span: Span, len = 0: usize)`: generates `span()` and `span_mut()` returning the
+///   field which every variant has. Named fields are found by the name, and tuple fields by the
+///   index after `=`. The variants lacking the field are listed in the compile error.
+/// - `serde(repr = "flat")` or `serde(repr = "structured")`: implements `Serialize` and
+///   `Deserialize`, which requires the `serde` feature. `"flat"`, the default of `serde`, uses
+///   the externally tagged variant names of the flat enum like `{"E1":5}`. `"structured"`
+///   serializes the value like the derived `Serialize` of the structured enum, like
+///   `{"Enum1":{"E1":5}}`, writing the variants of the nested enums directly, and deserializes
+///   it through the structured enum, which should implement `Deserialize`. The flattened enums
+///   should derive `Serialize` without renaming the variants, or be the flat enums with
+///   `serde(repr = "structured")`.
+/// - `codec`: implements [`codec::Encode`] and [`codec::Decode`], which write the single varint
+///   tag of the flat variant followed by the fields. It is also available in `#[into_flat]` and
+///   `#[flat_enum(..)]` on [`FlatTarget`], where each nesting level has its own tag.
+/// - `ffi`: generates `#[repr(C)]` struct `Enum2FlatFfi` which has the `u32` tag and the payload
+///   union of `#[repr(C)]` structs for the variants, the tag enum `Enum2FlatFfiTag`, and the
+///   conversions `to_ffi()` and `unsafe fn from_ffi()`, which requires the payload of the tag to
+///   be initialized. `Enum2FlatFfi::c_header()` returns the matching C declarations. The fields
+///   should implement [`ffi::FfiSafe`], which is checked at compile time, and the flat enum should
+///   not be generic.
+///
+/// ```
+/// # use flat_enum::{flat, into_flat, FlatTarget};
+/// # #[derive(FlatTarget)]
+/// # pub enum Enum1<A> {
+/// #     E1(A),
+/// #     E2(),
+/// #     E3(String),
+/// # }
+/// # #[into_flat(Enum2Flat<A>)]
+/// # pub enum Enum2<A> {
+/// #     #[flatten]
+/// #     Enum1(Enum1<A>),
+/// #     E4,
+/// # }
+/// #[flat(Enum2<A>, accessors)]
+/// pub enum Enum2Flat<A> {}
+///
+/// let v: Enum2Flat<u8> = Enum2Flat::E3("abc".to_owned());
+/// assert!(v.is_e3());
+/// assert_eq!(v.as_e3(), Some((&"abc".to_owned(),)));
+/// assert!(v.into_e1().is_err());
+/// ```
 pub use flat_enum_macro::flat;
 
 /// This attribute macro implements trait [`IntoFlat`] on the speficied enum.
@@ -56,11 +136,18 @@ pub use flat_enum_macro::flat;
 /// ## Arguments
 ///
 /// Specify the flat enum (which has [`Flat`] trait implementation implemented with [`flat`]
-/// macro) as the first arguments.
-/// The flat enum should be exist in the same context (same module, function, or block context).
+/// macro) as the first arguments. The flat enum may be defined in the other module, as long as
+/// it can be named by the path here.
+///
+/// If `flat_enum` is used through the other crate, specify the path of the crate with
+/// `#[flat_enum(crate = path)]` after `#[into_flat(..)]`, or using atmark syntax like
+/// `#[into_flat(Enum2Flat<A> @ ::flat_enum)]`. The flat enum uses the same path.
 ///
-/// You can specify the `flat_enum` crate using atmark syntax like
-/// `#[into_flat(Enum2Flat<A> @ ::flat_enum)]`.
+/// Options follow, separated with commas. `map(A)` generates `map()` and `to_ref()` like
+/// [`flat`]; the `#[flatten]` variants should also have them. `codec` implements
+/// [`codec::Encode`] and [`codec::Decode`], with the tag of the variant followed by the fields.
+/// `local` defines the helper macro with plain `macro_rules!` like `#[flat_enum(local)]` on
+/// [`FlatTarget`].
 ///
 /// ## `#[flatten]` attribute
 ///
@@ -68,15 +155,212 @@ pub use flat_enum_macro::flat;
 /// attribute is expanded in the corresponding flat enum. The variant should have tuple-like
 /// fields, and just one field with type, which has [`FlatTarget`] trait implementation defined
 /// with `#[derive(FlatTarget)]`.
+///
+/// ## Stable IDs
+///
+/// Variants of `#[into_flat]` and `#[derive(FlatTarget)]` enums can have the stable IDs with
+/// `#[flat_enum(id = N)]`, which do not change when the variants are reordered. The IDs of the
+/// flattened enum are shifted with `#[flatten(id_base = N)]`, so each group can have its own
+/// range. If the variants have the IDs, the flat enum gets `stable_id(&self) -> u32` and
+/// `from_stable_id(u32) -> Option<usize>`, which returns the index of the variant. All variants
+/// of the flat enum should have the IDs, without duplicates.
+///
+/// ```
+/// # use flat_enum::{flat, into_flat, FlatTarget};
+/// #[derive(FlatTarget)]
+/// pub enum Enum1 {
+///     #[flat_enum(id = 1)]
+///     E1,
+///     #[flat_enum(id = 2)]
+///     E2(u8),
+/// }
+///
+/// #[into_flat(Enum2Flat)]
+/// pub enum Enum2 {
+///     #[flatten(id_base = 100)]
+///     Enum1(Enum1),
+///     #[flat_enum(id = 1)]
+///     E3,
+/// }
+///
+/// #[flat(Enum2)]
+/// pub enum Enum2Flat {}
+///
+/// assert_eq!(Enum2Flat::E2(5).stable_id(), 102);
+/// assert_eq!(Enum2Flat::from_stable_id(1), Some(2));
+/// ```
 pub use flat_enum_macro::into_flat;
 
 /// Implements trait [`FlatTarget`] on the specified enum. This trait is required to be
 /// used as the field type of nesting enum variant augmented with `#[flatten]` attribute
 /// in `#[into_flat]` enum.
+///
+/// The enum gets the hidden helper macro of the same name, which is `#[macro_export]`ed only if
+/// the enum is `pub`, so that the enum can be flattened in other crates. Otherwise the macro has
+/// the same visibility as the enum. The helper macros are versioned with a protocol number, and
+/// accept only the protocol they are built with, not a range of them. If the enum is used from
+/// the crate depending on the version of `flat_enum` with another protocol, the error names both
+/// versions.
+/// The exported macro is named after the crate, the name, the tokens and, on Rust 1.88 or later,
+/// the location of the enum. On the older toolchains, or if the enums are expanded from the same
+/// place in a `macro_rules!` body, two identical `pub` enums of the same name in a crate are
+/// reported as defined multiple times.
+///
+/// If `flat_enum` is used through the other crate, specify the path of the crate with
+/// `#[flat_enum(crate = path)]`. The helper macro carries the path, so the crates flattening the
+/// enum need not to know it. The path starting with `crate` is resolved in the crate defining the
+/// enum.
+///
+/// With `#[flat_enum(local)]`, the helper macro is the textually scoped `macro_rules!` instead,
+/// so the enums can be defined in functions or blocks. In this case the enums should be defined
+/// before they are used in `#[flatten]` or [`flat`], in the same scope.
+///
+/// ```
+/// # use flat_enum::{flat, into_flat, FlatTarget};
+/// fn main() {
+///     #[derive(FlatTarget)]
+///     #[flat_enum(local)]
+///     enum Enum1 {
+///         E1(u8),
+///     }
+///
+///     #[into_flat(Enum2Flat, local)]
+///     enum Enum2 {
+///         #[flatten]
+///         Enum1(Enum1),
+///         E2,
+///     }
+///
+///     #[flat(Enum2)]
+///     enum Enum2Flat {}
+///
+///     let _ = Enum2Flat::E1(1);
+/// }
+/// ```
+///
+/// With `#[flat_enum(visitor)]`, the trait `EnumVisitor` is also generated, which has
+/// `visit_*(&mut self, ..)` method taking the references to the fields for each variant, and the
+/// enum gets `accept()` method which calls it. `#[flat_enum(map(A))]` generates `map()` and
+/// `to_ref()` like [`flat`], and `#[flat_enum(codec)]` implements [`codec::Encode`] and
+/// [`codec::Decode`].
+///
+/// ```
+/// # use flat_enum::FlatTarget;
+/// #[derive(FlatTarget)]
+/// #[flat_enum(visitor)]
+/// pub enum Enum1<A> {
+///     E1(A),
+///     E2 { x: u8 },
+/// }
+///
+/// struct Sum(u32);
+///
+/// impl Enum1Visitor<u32> for Sum {
+///     fn visit_e1(&mut self, a0: &u32) {
+///         self.0 += a0;
+///     }
+///     fn visit_e2(&mut self, x: &u8) {
+///         self.0 += *x as u32;
+///     }
+/// }
+///
+/// let mut sum = Sum(0);
+/// Enum1::E1(1).accept(&mut sum);
+/// Enum1::E2 { x: 2 }.accept(&mut sum);
+/// assert_eq!(sum.0, 3);
+/// ```
 pub use flat_enum_macro::FlatTarget;
 
-/// Marker trait implemented with `#[derive(FlatTarget)]`.
-pub unsafe trait FlatTarget {}
+/// Makes the trait available in `#[flat(.., delegate(Trait))]`, which implements the trait on
+/// the flat enum by forwarding each method to the payload of the variant.
+///
+/// ```
+/// # use flat_enum::{delegatable, flat, into_flat, FlatTarget};
+/// #[delegatable]
+/// pub trait Handler {
+///     fn handle(&self, input: u32) -> u32;
+/// }
+///
+/// pub struct Double;
+///
+/// impl Handler for Double {
+///     fn handle(&self, input: u32) -> u32 {
+///         input * 2
+///     }
+/// }
+///
+/// pub struct Add(u32);
+///
+/// impl Handler for Add {
+///     fn handle(&self, input: u32) -> u32 {
+///         input + self.0
+///     }
+/// }
+///
+/// #[derive(FlatTarget)]
+/// pub enum Enum1 {
+///     Double(Double),
+/// }
+///
+/// #[into_flat(Enum2Flat)]
+/// pub enum Enum2 {
+///     #[flatten]
+///     Enum1(Enum1),
+///     Add(Add),
+/// }
+///
+/// #[flat(Enum2, delegate(Handler))]
+/// pub enum Enum2Flat {}
+///
+/// assert_eq!(Enum2Flat::Double(Double).handle(3), 6);
+/// assert_eq!(Enum2Flat::Add(Add(1)).handle(3), 4);
+/// ```
+///
+/// Methods should take `self`, `&self` or `&mut self`, except the ones with the default
+/// implementation. The methods with the receiver should not mention `Self` in the return type
+/// or the other arguments, because the payloads have the different types. Generic parameters of
+/// the trait become the parameters of the implementation. Associated types and associated
+/// constants without defaults are not supported.
+///
+/// The trait gets the hidden helper macro of the same name next to it, like [`FlatTarget`], which
+/// `delegate(Trait)` calls through the path of the trait. If the trait is `pub`, the macro is
+/// `#[macro_export]`ed under a hashed name, so it also appears in the crate root, and the trait can
+/// be delegated in other crates. Otherwise the macro has the same visibility as the trait.
+///
+/// The trait should exist in the same context as the flat enum, or be specified with the
+/// path. You can specify the `flat_enum` crate like `#[delegatable(crate = ::flat_enum)]`, which
+/// is used by the flat enums delegating the trait.
+pub use flat_enum_macro::delegatable;
+
+#[doc(hidden)]
+pub use flat_enum_macro::flat_impl;
+
+#[doc(hidden)]
+pub use flat_enum_macro::structured_impl;
+
+#[doc(hidden)]
+pub use flat_enum_macro::delegate_impl;
+
+pub mod codec;
+pub mod ffi;
+mod descriptor;
+
+pub use descriptor::{
+    describe, Describe, EnumDescriptor, EnumKind, FieldDescriptor, FieldsKind,
+    FlatDescription, FlatVariantDescription, VariantDescriptor,
+};
+
+/// Marker trait implemented with `#[derive(FlatTarget)]`, which also implements [`Describe`].
+///
+/// # Safety
+///
+/// This trait should be implemented only with `#[derive(FlatTarget)]`.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` does not implement `flat_enum::FlatTarget`",
+    label = "the type of the `#[flatten]` field",
+    note = "add `#[derive(FlatTarget)]` to the enum to flatten it"
+)]
+pub unsafe trait FlatTarget: Describe {}
 
 /// Leak `N`th type used in enum variants.
 #[doc(hidden)]
@@ -84,26 +368,231 @@ pub unsafe trait Leak<const N: usize, EnumTypeParams>: FlatTarget {
     type Ty;
 }
 
+/// Leak `N`th type used in the variants of the flat enum, implemented on the structured enum.
+#[doc(hidden)]
+pub unsafe trait LeakFlat<const N: usize> {
+    type Ty;
+}
+
+/// Defines the macro `$name` which holds the variants of the flat enum, collected by
+/// [`__next_part!`] from a chunk of the helper macros of the flattened enums. The macros are merged
+/// by `@merge`, which defines the macro `$out` holding the variants of both, so that the chunks are
+/// collected in the same depth of the expansion. `@finish` passes the variants to `$mac!` after
+/// `$args`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_part {
+    (($d:tt) $name:ident { $($variants:tt)* }) => {
+        macro_rules! $name {
+            (@merge ($d dd:tt) $d right:ident $d out:ident) => {
+                $d right! { @prepend ($d dd) $d out { $($variants)* } }
+            };
+            (@prepend ($d dd:tt) $d out:ident { $d ($d left:tt)* }) => {
+                $crate::__define_part! { ($d dd) $d out { $d ($d left)* $($variants)* } }
+            };
+            (@finish { $d ($d mac:tt)* } { $d ($d args:tt)* }) => {
+                $d ($d mac)* ! { $d ($d args)* { $($variants)* } }
+            };
+        }
+    };
+}
+
+/// Collects the variants of the flat enum in `{ $($variants)* }` by calling the helper macros in the
+/// brackets one after another. Each entry is the normal variants before the `#[flatten]` variant,
+/// the helper macro of its type and the arguments, and the helper macro calls `__next_part!` again
+/// with its own variants added, so that nothing refers to the variants of a helper macro which is
+/// not defined. After the last normal variants, the variants are defined as the part `$part`, or
+/// passed to `$mac!` after `$args` like `@finish` of the part.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __next_part {
+    (($d:tt) $part:ident { $($variants:tt)* } [{ $($normal:tt)* } ($($mac:tt)*) ($($header:tt)*) [$($arg:tt)*] $($rest:tt)*]) => {
+        $($mac)* ! { @emit_part $($header)* ($d) $part [$($arg)*] { $($variants)* $($normal)* } [$($rest)*] }
+    };
+    (($d:tt) $part:ident { $($variants:tt)* } [{ $($normal:tt)* }]) => {
+        $crate::__define_part! { ($d) $part { $($variants)* $($normal)* } }
+    };
+    (($d:tt) $part:ident { $($variants:tt)* } [{ $($normal:tt)* } => { $($mac:tt)* } { $($args:tt)* }]) => {
+        $($mac)* ! { $($args)* { $($variants)* $($normal)* } }
+    };
+}
+
 /// See [`into_flat`]
+///
+/// # Safety
+///
+/// This trait should be implemented only with [`into_flat`] macro.
 pub unsafe trait IntoFlat {
     type Flat: Flat<Structured = Self>;
     fn into_flat(self) -> Self::Flat;
     fn from_flat(_: Self::Flat) -> Self;
 }
 
+/// Runtime information of the variants, implemented with `#[derive(FlatTarget)]`, [`into_flat`]
+/// and [`flat`].
+///
+/// ```
+/// # use flat_enum::{flat, into_flat, FlatTarget, VariantInfo};
+/// # #[derive(FlatTarget)]
+/// # pub enum Enum1<A> {
+/// #     E1(A),
+/// #     E2(),
+/// #     E3(String),
+/// # }
+/// #[into_flat(Enum2Flat<A>)]
+/// pub enum Enum2<A> {
+///     #[flatten]
+///     Enum1(Enum1<A>),
+///     E4,
+/// }
+///
+/// #[flat(Enum2<A>)]
+/// pub enum Enum2Flat<A> {}
+///
+/// let v: Enum2Flat<u8> = Enum2Flat::E3("abc".to_owned());
+/// assert_eq!(v.variant_name(), "E3");
+/// assert_eq!(v.variant_index(), 2);
+/// assert_eq!(v.origin_path(), &["Enum1", "E3"]);
+/// assert_eq!(Enum2Flat::<u8>::VARIANT_COUNT, 4);
+/// assert_eq!(Enum2::<u8>::VARIANT_NAMES, &["Enum1", "E4"]);
+/// ```
+pub trait VariantInfo {
+    /// Names of the variants, in the declaration order.
+    const VARIANT_NAMES: &'static [&'static str];
+
+    /// The number of the variants.
+    const VARIANT_COUNT: usize = Self::VARIANT_NAMES.len();
+
+    /// Returns the position of the variant in the declaration.
+    fn variant_index(&self) -> usize;
+
+    /// Returns the name of the variant.
+    fn variant_name(&self) -> &'static str {
+        Self::VARIANT_NAMES[self.variant_index()]
+    }
+
+    /// Returns the names of `#[flatten]` variants which the variant comes through, followed by
+    /// the name of the variant itself. It only contains the variant name except on flat enums.
+    fn origin_path(&self) -> &'static [&'static str] {
+        core::slice::from_ref(&Self::VARIANT_NAMES[self.variant_index()])
+    }
+}
+
 /// See [`flat`]
+///
+/// # Safety
+///
+/// This trait should be implemented only with [`flat`] macro.
 pub unsafe trait Flat {
     type Structured: IntoFlat<Flat = Self>;
 }
 
+#[doc(hidden)]
+pub mod __private {
+    use crate::EnumDescriptor;
+    use core::fmt;
+
+    /// Prints the variant of the flat enum as the structured enum, following the origin path.
+    pub struct DebugStructured<'a> {
+        descriptor: &'static EnumDescriptor,
+        path: &'static [&'static str],
+        fields: &'a dyn Fn(&mut fmt::Formatter<'_>, &'static str) -> fmt::Result,
+    }
+
+    impl<'a> DebugStructured<'a> {
+        pub fn new(
+            descriptor: &'static EnumDescriptor,
+            path: &'static [&'static str],
+            fields: &'a dyn Fn(&mut fmt::Formatter<'_>, &'static str) -> fmt::Result,
+        ) -> Self {
+            Self {
+                descriptor,
+                path,
+                fields,
+            }
+        }
+    }
+
+    impl fmt::Debug for DebugStructured<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}::", self.descriptor.name)?;
+            let inner = self.descriptor.variants.iter().find_map(|variant| {
+                (variant.name == self.path[0])
+                    .then_some(variant.flatten)
+                    .flatten()
+            });
+            match (inner, self.path.len()) {
+                (Some(descriptor), 2..) => f
+                    .debug_tuple(self.path[0])
+                    .field(&DebugStructured::new(
+                        descriptor,
+                        &self.path[1..],
+                        self.fields,
+                    ))
+                    .finish(),
+                _ => (self.fields)(f, self.path[0]),
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub use serde;
+
+    /// Serializes `value` in the newtype variants of `levels`, which are the names of the enums,
+    /// the indices and the names of the variants, from the outermost one.
+    #[cfg(feature = "serde")]
+    pub struct SerializeNested<'a, T> {
+        pub levels: &'static [(&'static str, u32, &'static str)],
+        pub value: &'a T,
+    }
+
+    #[cfg(feature = "serde")]
+    impl<T: serde::Serialize> serde::Serialize for SerializeNested<'_, T> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self.levels.split_first() {
+                Some((&(name, index, variant), levels)) => serializer.serialize_newtype_variant(
+                    name,
+                    index,
+                    variant,
+                    &SerializeNested {
+                        levels,
+                        value: self.value,
+                    },
+                ),
+                None => self.value.serialize(serializer),
+            }
+        }
+    }
+
+    /// Implemented on the enums with `map(..)`, which `map()` and `to_ref()` of the other enums
+    /// can map in the fields.
+    #[diagnostic::on_unimplemented(
+        message = "`{Self}` cannot be mapped in the fields",
+        label = "this field type",
+        note = "expected the type parameter in `Vec`, `Option`, `[_; N]`, or the enum with `map(..)`"
+    )]
+    pub trait MapEnum {}
+
+    pub fn assert_map_enum<T: MapEnum + ?Sized>() {}
+
+    /// The description of the `#[flatten]` field type. Only `FlatTarget` is required, so the
+    /// missing `#[derive(FlatTarget)]` is reported once on the field type.
+    pub const fn describe_flatten<T: ?Sized + crate::FlatTarget>() -> &'static EnumDescriptor {
+        T::DESCRIPTOR
+    }
+}
+
 #[cfg(feature = "testing")]
 pub mod testing {
-    use super::FlatTarget;
+    use super::{FlatTarget, IntoFlat};
+    use core::fmt::Debug;
+    use core::hash::{Hash, Hasher};
+    use std::collections::hash_map::DefaultHasher;
 
     macro_rules! emit_enum6 {
         () => {
             #[derive(FlatTarget)]
-            #[flat_enum($crate)]
+            #[flat_enum(crate = $crate)]
             pub enum Enum6<'a, const N: usize, A> {
                 E8(&'a [A; N]),
             }
@@ -111,4 +600,31 @@ pub mod testing {
     }
 
     emit_enum6!();
+
+    fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Asserts that the traits implemented with `#[flat(.., structured(..))]` agree with the
+    /// structured enum, on every pair of `values`.
+    pub fn assert_consistent<S>(values: &[S])
+    where
+        S: IntoFlat + Clone + Ord + Hash + Debug + PartialEq<S::Flat>,
+        S::Flat: Ord + Hash + Debug + PartialEq<S>,
+    {
+        for a in values {
+            let fa = a.clone().into_flat();
+            assert_eq!(hash(a), hash(&fa), "hash of {:?}", a);
+            for b in values {
+                let fb = b.clone().into_flat();
+                assert_eq!(a == b, fa == fb, "{:?} == {:?}", a, b);
+                assert_eq!(a == b, fa == *b, "{:?} == {:?}", a, b);
+                assert_eq!(a == b, *a == fb, "{:?} == {:?}", a, b);
+                assert_eq!(a.cmp(b), fa.cmp(&fb), "{:?} cmp {:?}", a, b);
+                assert_eq!(a.partial_cmp(b), fa.partial_cmp(&fb), "{:?} cmp {:?}", a, b);
+            }
+        }
+    }
 }
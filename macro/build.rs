@@ -0,0 +1,8 @@
+// `proc_macro::Span::{file, line, column}` are stable since Rust 1.88. With them, the names of the
+// generated helper macros also depend on the location of the item.
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(flat_enum_span_location)");
+    if version_check::is_min_version("1.88.0").unwrap_or(false) {
+        println!("cargo:rustc-cfg=flat_enum_span_location");
+    }
+}
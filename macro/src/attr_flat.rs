@@ -1,46 +1,232 @@
-use derive_syn_parse::Parse;
+use crate::cmp;
+use crate::codec;
+use crate::common::{self, CommonField};
+use crate::delegate;
+use crate::derive_macro;
+use crate::descriptor;
+use crate::ffi;
+use crate::fmt;
+use crate::map;
+use crate::origin::{self, Origin, StructuredVariant};
+use crate::serde::{self, Repr};
+use crate::util::{
+    emit_pattern, field_ids, path_without_arguments, protocol_header, remove_derive, to_snake_case,
+};
+use crate::variant_info;
+use crate::visitor;
 use proc_macro2::TokenStream;
 use proc_macro_error::abort;
+use syn::parse::{Parse, ParseBuffer, ParseStream};
+use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::*;
 use template_quote::quote;
 
-#[derive(Parse)]
+#[derive(Default)]
+pub struct FlatOptions {
+    accessors: bool,
+    debug_structured: bool,
+    structured: Vec<Ident>,
+    delegate: Vec<Path>,
+    visitor: bool,
+    map: Option<Ident>,
+    common: Vec<CommonField>,
+    serde: Option<Repr>,
+    codec: bool,
+    ffi: bool,
+}
+
+/// Parses the parenthesized arguments of the option `ident`. The error is reported at `ident` if
+/// the parentheses are missing.
+fn option_args<'a>(ident: &Ident, input: ParseStream<'a>) -> Result<ParseBuffer<'a>> {
+    if !input.peek(token::Paren) {
+        return Err(Error::new(
+            ident.span(),
+            format!("Expected `{}(..)`", ident),
+        ));
+    }
+    let content;
+    parenthesized!(content in input);
+    Ok(content)
+}
+
+impl FlatOptions {
+    fn parse_option(&mut self, input: ParseStream) -> Result<()> {
+        let ident: Ident = input.parse()?;
+        match ident.to_string().as_str() {
+            "accessors" => self.accessors = true,
+            "visitor" => self.visitor = true,
+            "codec" => self.codec = true,
+            "ffi" => self.ffi = true,
+            "serde" => {
+                self.serde = Some(if input.peek(token::Paren) {
+                    let content;
+                    parenthesized!(content in input);
+                    content.parse()?
+                } else {
+                    Repr::Flat
+                });
+            }
+            "common" => {
+                let content = option_args(&ident, input)?;
+                self.common
+                    .extend(Punctuated::<CommonField, Token![,]>::parse_terminated(&content)?);
+            }
+            "map" => {
+                let content = option_args(&ident, input)?;
+                self.map = Some(content.parse()?);
+            }
+            "debug" => {
+                if !input.peek(Token![=]) {
+                    return Err(Error::new(
+                        ident.span(),
+                        "Expected `debug = \"structured\"`",
+                    ));
+                }
+                input.parse::<Token![=]>()?;
+                let lit: LitStr = input.parse()?;
+                if lit.value() != "structured" {
+                    return Err(Error::new(lit.span(), "Expected \"structured\""));
+                }
+                self.debug_structured = true;
+            }
+            "structured" => {
+                let content = option_args(&ident, input)?;
+                for tr in Punctuated::<Ident, Token![,]>::parse_terminated(&content)? {
+                    if !cmp::TRAITS.contains(&tr.to_string().as_str()) {
+                        return Err(Error::new(
+                            tr.span(),
+                            format!("Expected one of {}", cmp::TRAITS.join(", ")),
+                        ));
+                    }
+                    self.structured.push(tr);
+                }
+            }
+            "delegate" => {
+                let content = option_args(&ident, input)?;
+                for path in Punctuated::<Path, Token![,]>::parse_terminated(&content)? {
+                    if path.segments.iter().any(|seg| !seg.arguments.is_empty()) {
+                        return Err(Error::new(
+                            path.span(),
+                            "Generic arguments of the trait cannot be specified",
+                        ));
+                    }
+                    self.delegate.push(path);
+                }
+            }
+            _ => return Err(Error::new(ident.span(), "Unknown option for #[flat]")),
+        }
+        Ok(())
+    }
+}
+
 pub struct MacroArg {
     structured_path: Path,
-    _at_token: Option<Token![@]>,
-    #[parse_if(_at_token.is_some())]
     krate: Option<Path>,
+    options: FlatOptions,
+    /// Tokens of the options, which are passed to `#[flat_impl]`.
+    options_tokens: TokenStream,
 }
 
-pub fn flat(arg: MacroArg, input: ItemEnum) -> TokenStream {
-    let MacroArg {
-        structured_path,
-        krate,
-        ..
-    } = arg;
-    let krate = krate.unwrap_or(parse_quote!(::flat_enum));
-    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
-    let macro_name =
-        if structured_path.leading_colon.is_none() && structured_path.segments.len() == 1 {
-            &structured_path.segments[0].ident
+impl Parse for MacroArg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let structured_path = input.parse()?;
+        let krate = if input.parse::<Option<Token![@]>>()?.is_some() {
+            Some(input.parse()?)
         } else {
-            abort!(
-                structured_path.span(),
-                "Should be exist in the same context"
-            );
+            None
         };
-    if input.variants.len() > 0 {
-        abort!(input.span(), "Cannot specify variants");
+        let options_tokens = input.cursor().token_stream();
+        let mut options = FlatOptions::default();
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            options.parse_option(input)?;
+        }
+        Ok(Self {
+            structured_path,
+            krate,
+            options,
+            options_tokens,
+        })
     }
+}
+
+impl MacroArg {
+    fn to_tokens(&self) -> TokenStream {
+        quote! {
+            #{&self.structured_path}
+            #(if let Some(krate) = &self.krate) { @ #krate }
+            #{&self.options_tokens}
+        }
+    }
+}
+
+fn emit_accessors(input: &ItemEnum) -> TokenStream {
+    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
     quote! {
         #[automatically_derived]
-        unsafe impl #g_impl #krate::Flat for #{ &input.ident } #g_type #g_where {
-            type Structured = #structured_path;
+        #[allow(unreachable_patterns, clippy::type_complexity)]
+        impl #g_impl #{&input.ident} #g_type #g_where {
+            #(for variant in &input.variants) {
+                #(let name = to_snake_case(&variant.ident)) {
+                    #(let ids = field_ids(variant, "a")) {
+                        #(let pat = emit_pattern(&quote!(Self), variant, &ids)) {
+                            #(let tys = variant.fields.iter().map(|field| &field.ty).collect::<Vec<_>>()) {
+                                #[doc = #{format!("Returns `true` if the value is `{}::{}`.", &input.ident, &variant.ident)}]
+                                #{&input.vis} fn #{Ident::new(&format!("is_{}", name), variant.ident.span())}(&self) -> bool {
+                                    matches!(self, Self :: #{&variant.ident} { .. })
+                                }
+
+                                #[doc = #{format!("Returns references to the fields if the value is `{}::{}`.", &input.ident, &variant.ident)}]
+                                #{&input.vis} fn #{Ident::new(&format!("as_{}", name), variant.ident.span())}(&self) -> Option<( #(& #tys,)* )> {
+                                    match self {
+                                        #pat => Some(( #(#ids,)* )),
+                                        _ => None,
+                                    }
+                                }
+
+                                #[doc = #{format!("Returns mutable references to the fields if the value is `{}::{}`.", &input.ident, &variant.ident)}]
+                                #{&input.vis} fn #{Ident::new(&format!("as_{}_mut", name), variant.ident.span())}(&mut self) -> Option<( #(&mut #tys,)* )> {
+                                    match self {
+                                        #pat => Some(( #(#ids,)* )),
+                                        _ => None,
+                                    }
+                                }
+
+                                #[doc = #{format!("Returns the fields if the value is `{}::{}`, otherwise returns `self` as an error.", &input.ident, &variant.ident)}]
+                                #{&input.vis} fn #{Ident::new(&format!("into_{}", name), variant.ident.span())}(self) -> Result<( #(#tys,)* ), Self> {
+                                    match self {
+                                        #pat => Ok(( #(#ids,)* )),
+                                        this => Err(this),
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
         }
-        #macro_name!(
+    }
+}
+
+pub fn flat(arg: MacroArg, input: ItemEnum) -> TokenStream {
+    let structured_path = &arg.structured_path;
+    let (_, g_type, _) = input.generics.split_for_impl();
+    // The helper macro is found by the same path as the structured enum.
+    let macro_path = path_without_arguments(structured_path);
+    if !input.variants.is_empty() {
+        abort!(input.span(), "Cannot specify variants");
+    }
+    // The helper macro of the structured enum adds `#[flat_impl]` with the path of the crate.
+    quote! {
+        #macro_path!(
             @emit_enum
-            flat_enum = #{env!("CARGO_PKG_VERSION")},
+            #{protocol_header()}
+            structured = { #structured_path },
+            args = { #{arg.to_tokens()} },
             enum_decl = {
                 #(for attr in &input.attrs) { #attr }
                 #{&input.vis}
@@ -51,3 +237,111 @@ pub fn flat(arg: MacroArg, input: ItemEnum) -> TokenStream {
         );
     }
 }
+
+/// Arguments of `#[flat_impl]`, which are the path of the crate given by the structured enum, the
+/// variants of the structured enum in brackets and the arguments of `#[flat]`, separated with `;`.
+pub struct ImplArg {
+    krate: Path,
+    variants: Vec<StructuredVariant>,
+    /// The structured enum has custom discriminants.
+    discriminant: bool,
+    arg: MacroArg,
+}
+
+impl Parse for ImplArg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let krate = input.parse()?;
+        input.parse::<Token![;]>()?;
+        let content;
+        bracketed!(content in input);
+        let variants = Punctuated::<StructuredVariant, Token![,]>::parse_terminated(&content)?
+            .into_iter()
+            .collect();
+        input.parse::<Token![;]>()?;
+        let discriminant: LitBool = input.parse()?;
+        input.parse::<Token![;]>()?;
+        Ok(Self {
+            krate,
+            variants,
+            discriminant: discriminant.value,
+            arg: input.parse()?,
+        })
+    }
+}
+
+/// Called on the expanded flat enum, which the variants are filled in.
+pub fn flat_impl(
+    ImplArg {
+        krate,
+        variants,
+        discriminant,
+        arg,
+    }: ImplArg,
+    mut input: ItemEnum,
+) -> TokenStream {
+    let options = derive_macro::DeriveOptions::from_attrs(&input.attrs);
+    input.attrs.retain(|attr| !attr.path.is_ident("flat_enum"));
+    // The path given to the flat enum precedes the one of the structured enum.
+    let krate = arg.krate.clone().or(options.krate.clone()).unwrap_or(krate);
+    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
+    let mut origins: Vec<_> = input.variants.iter_mut().map(Origin::take).collect();
+    origin::check_ids(&input, &origins);
+    origin::add_structured_level(
+        &input,
+        &mut origins,
+        &arg.structured_path,
+        &variants,
+        discriminant,
+    );
+    // `#[derive(FlatTarget)]` on the flat enum is processed here, to pass the origins.
+    let mut visitor = arg.options.visitor;
+    let mut map_param = arg.options.map.clone();
+    let mut codec = arg.options.codec;
+    let flat_target = if remove_derive(&mut input.attrs, "FlatTarget") {
+        visitor |= options.visitor;
+        map_param = map_param.or(options.map);
+        codec |= options.codec;
+        derive_macro::emit_flat_target(&input, &krate, &origins, options.local)
+    } else {
+        TokenStream::new()
+    };
+    quote! {
+        #input
+        #[automatically_derived]
+        unsafe impl #g_impl #krate::Flat for #{ &input.ident } #g_type #g_where {
+            type Structured = #{&arg.structured_path};
+        }
+        #flat_target
+        #{variant_info::emit(&input, &krate, Some(&origins))}
+        #{variant_info::emit_stable_id(&input, &origins)}
+        #{descriptor::emit_flat(&input, &krate, &arg.structured_path)}
+        #(if arg.options.accessors) {
+            #{emit_accessors(&input)}
+        }
+        #(if arg.options.debug_structured) {
+            #{fmt::emit_debug_structured(&input, &krate, &arg.structured_path)}
+        }
+        #{cmp::emit(&input, &arg.structured_path, &origins, &arg.options.structured)}
+        #(for common in &arg.options.common) {
+            #{common::emit(&input, common)}
+        }
+        #(if visitor) {
+            #{visitor::emit(&input, &origins)}
+        }
+        #(if let Some(param) = &map_param) {
+            #{map::emit(&input, &krate, param, &map::field_shapes(&input, &origins))}
+        }
+        #(for trait_path in &arg.options.delegate) {
+            #{delegate::emit(&input, trait_path)}
+        }
+        #(if let Some(repr) = arg.options.serde) {
+            #{serde::emit(&input, &krate, &arg.structured_path, &origins, repr)}
+        }
+        #(if codec) {
+            #{codec::emit(&input, &krate)}
+        }
+        #(if arg.options.ffi) {
+            #{ffi::emit(&input, &krate)}
+        }
+    }
+}
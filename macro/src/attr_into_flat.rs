@@ -1,13 +1,55 @@
-use crate::util::{generic_arg_to_type, getrandom};
-use derive_syn_parse::Parse;
+use crate::cmp;
+use crate::codec;
+use crate::derive_macro::DeriveOptions;
+use crate::descriptor;
+use crate::map;
+use crate::origin::{self, Origin, StructuredVariant};
+use crate::util::{
+    emit_helper_macro, emit_pattern, field_ids, generic_arg_to_type, item_hash, path_in_macro,
+    path_without_arguments, protocol_header, tokens_to_string,
+};
+use crate::variant_info;
 use proc_macro2::{Span, TokenStream};
 use proc_macro_error::abort;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::*;
 use template_quote::quote;
 
 fn attribute_is_flatten(attr: &Attribute) -> bool {
-    &attr.style == &AttrStyle::Outer && attr.path.is_ident("flatten")
+    attr.style == AttrStyle::Outer && attr.path.is_ident("flatten")
+}
+
+mod kw {
+    syn::custom_keyword!(id_base);
+}
+
+/// `id_base = N` in `#[flatten(..)]`.
+struct FlattenArg {
+    id_base: LitInt,
+}
+
+impl Parse for FlattenArg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<kw::id_base>()?;
+        input.parse::<Token![=]>()?;
+        let id_base: LitInt = input.parse()?;
+        id_base.base10_parse::<u32>()?;
+        Ok(Self { id_base })
+    }
+}
+
+/// Returns `id_base` in `#[flatten(id_base = N)]`.
+fn flatten_id_base(attrs: &[Attribute]) -> Option<LitInt> {
+    let attr = attrs.iter().find(|attr| attribute_is_flatten(attr))?;
+    if attr.tokens.is_empty() {
+        return None;
+    }
+    match attr.parse_args::<FlattenArg>() {
+        Ok(arg) => Some(arg.id_base),
+        Err(e) => abort!(e.span(), "{}", e),
+    }
 }
 
 fn split_path_param(mut path: Path) -> (Path, Vec<Type>) {
@@ -17,7 +59,11 @@ fn split_path_param(mut path: Path) -> (Path, Vec<Type>) {
                 args.args.iter().cloned().map(generic_arg_to_type).collect()
             }
             PathArguments::None => Vec::new(),
-            _ => panic!(),
+            PathArguments::Parenthesized(args) => abort!(
+                args.span(),
+                "parenthesized generic arguments are not supported";
+                help = "The type of the `#[flatten]` field should be the enum, like `Enum1<A>`"
+            ),
         };
         seg.arguments = PathArguments::None;
         v
@@ -27,7 +73,7 @@ fn split_path_param(mut path: Path) -> (Path, Vec<Type>) {
     (path, v)
 }
 
-#[allow(unused)]
+#[allow(unused, clippy::large_enum_variant)]
 enum ParsedVariant {
     Normal(Variant),
     Flattened {
@@ -39,279 +85,515 @@ enum ParsedVariant {
         macro_path: Path,
         discriminant: Option<(syn::token::Eq, Expr)>,
         arg_tys: Vec<Type>,
+        id_base: Option<LitInt>,
     },
 }
 
 impl ParsedVariant {
     fn new(variant: Variant) -> Self {
-        if variant.attrs.iter().any(attribute_is_flatten) {
-            match &variant.fields {
-                Fields::Unnamed(field)
-                    if field.unnamed.len() == 1 && field.unnamed[0].attrs.len() == 0 =>
-                {
-                    let paren_token = field.paren_token.clone();
-                    let field = &field.unnamed[0];
-                    match &field.ty {
-                        Type::Path(tp) if tp.qself.is_none() => {
-                            let (macro_path, arg_tys) = split_path_param(tp.path.clone());
-                            return ParsedVariant::Flattened {
-                                attrs: variant
-                                    .attrs
-                                    .into_iter()
-                                    .filter(|attr| !attribute_is_flatten(attr))
-                                    .collect(),
-                                ident: variant.ident,
-                                paren_token,
-                                ty: field.ty.clone(),
-                                macro_path,
-                                discriminant: variant.discriminant.clone(),
-                                arg_tys,
-                            };
-                        }
-                        _ => (),
-                    }
-                }
-                _ => (),
-            }
-            abort!(variant.span(), "Bad variant for #[flatten]")
-        } else {
-            ParsedVariant::Normal(variant)
+        if !variant.attrs.iter().any(attribute_is_flatten) {
+            return ParsedVariant::Normal(variant);
+        }
+        let help =
+            "The `#[flatten]` variant should have one field of the enum, like `Enum1(Enum1<A>)`";
+        let field = match &variant.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => fields,
+            Fields::Unnamed(fields) => abort!(
+                fields.span(),
+                "expected exactly one unnamed field; found {}",
+                fields.unnamed.len();
+                help = help
+            ),
+            Fields::Named(fields) => abort!(
+                fields.span(),
+                "expected exactly one unnamed field; found named fields";
+                help = help
+            ),
+            Fields::Unit => abort!(
+                variant.ident.span(),
+                "expected exactly one unnamed field; found the unit variant";
+                help = help
+            ),
+        };
+        let paren_token = field.paren_token;
+        let field = &field.unnamed[0];
+        if let Some(attr) = field.attrs.first() {
+            abort!(
+                attr.span(),
+                "attributes on the field of the `#[flatten]` variant are not supported"
+            );
+        }
+        let macro_path = match &field.ty {
+            Type::Path(tp) if tp.qself.is_none() => tp.path.clone(),
+            Type::Path(tp) => abort!(
+                tp.span(),
+                "qualified paths are not supported in the `#[flatten]` variant";
+                help = help
+            ),
+            ty => abort!(
+                ty.span(),
+                "expected the path to the enum; found `{}`",
+                tokens_to_string(quote!(#ty));
+                help = help
+            ),
+        };
+        let (macro_path, arg_tys) = split_path_param(macro_path);
+        let id_base = flatten_id_base(&variant.attrs);
+        ParsedVariant::Flattened {
+            attrs: variant
+                .attrs
+                .into_iter()
+                .filter(|attr| !attribute_is_flatten(attr))
+                .collect(),
+            ident: variant.ident,
+            paren_token,
+            ty: field.ty.clone(),
+            macro_path,
+            discriminant: variant.discriminant.clone(),
+            arg_tys,
+            id_base,
         }
     }
 }
 
-fn serialize_variants(variants: &[ParsedVariant], is_pat: bool) -> (TokenStream, Option<&Path>) {
-    let mut first_macro_path = None;
-    let mut out = TokenStream::new();
+/// Emits `variant` of the flat enum, whose field types are given by `ty`.
+fn emit_variant(variant: &Variant, ty: impl Fn(&Type) -> TokenStream) -> TokenStream {
+    let fields = variant
+        .fields
+        .iter()
+        .map(|field| {
+            quote! {
+                #(for attr in &field.attrs) { #attr }
+                #{&field.vis}
+                #(if let Some(ident) = &field.ident) { #ident: }
+                #{ty(&field.ty)}
+            }
+        })
+        .collect::<Vec<_>>();
+    quote! {
+        #(for attr in &variant.attrs) { #attr }
+        #{&variant.ident}
+        #(if let Fields::Named(_) = &variant.fields) { { #(#fields),* } }
+        #(if let Fields::Unnamed(_) = &variant.fields) { ( #(#fields),* ) }
+        #(if let Some((eq, expr)) = &variant.discriminant) { #eq #expr }
+    }
+}
+
+/// The number of the `#[flatten]` variants whose helper macros are called one after another.
+const CHUNK: usize = 32;
+
+/// Emits the variants of the flat enum, which are the normal variants and the variants given by
+/// the helper macros of the `#[flatten]` variants, and passes them to `structured_impl!` after
+/// `args`.
+///
+/// The helper macros are called in chunks by `__next_part!`, each passing the variants collected
+/// so far to the next one, so that a missing helper macro stops its chunk without other errors. The
+/// depth of the expansion grows with the chunk, so the chunks are defined as the parts with
+/// `__define_part!` and merged pairwise into the new parts until one is left. Each merge takes a
+/// fixed number of steps, so enums with hundreds of `#[flatten]` variants expand within the
+/// default `recursion_limit`. Enums with a single chunk pass the variants directly.
+fn emit_parts(
+    input: &ItemEnum,
+    variants: &[ParsedVariant],
+    krate: &Path,
+    args: &TokenStream,
+) -> TokenStream {
+    let hash = item_hash(&input.ident, input);
+    let mut count = 0;
+    let mut fresh = || {
+        count += 1;
+        Ident::new(
+            &format!("__flat_enum_part_{:x}_{}", hash, count - 1),
+            Span::call_site(),
+        )
+    };
+    let mut entries = Vec::new();
+    let mut normal = Vec::new();
     for variant in variants {
         match variant {
-            ParsedVariant::Normal(variant) => {
-                match (&variant.fields, is_pat) {
-                    (Fields::Unnamed(_), true) => {
-                        out.extend(quote!{{
-                            #{&variant.ident} (
-                                #(for i in 0..variant.fields.len()), {
-                                    #{Ident::new(&format!("a{}", i), Span::call_site())}
-                                }
-                            )
-                        }});
-                    }
-                    (Fields::Named(_), true) => {
-                        out.extend(quote!{{
-                            #{&variant.ident} {
-                                #(for field in &variant.fields), {
-                                    #{&field.ident}
-                                }
-                            }
-                        }})
-                    }
-                    _ => {
-                        out.extend(quote! {{#variant}});
-                    }
-                }
-            }
+            ParsedVariant::Normal(variant) => normal.push(variant),
             ParsedVariant::Flattened {
                 ident,
                 ty,
                 macro_path,
                 arg_tys,
+                id_base,
                 ..
             } => {
-                if first_macro_path.is_none() {
-                    first_macro_path = Some(macro_path);
-                    out.extend(quote! { @ [ #ident, (#ty), #(#arg_tys),* ] });
-                } else {
-                    out.extend(quote! { (#macro_path) [ #ident, (#ty), #(#arg_tys),* ] })
-                }
+                entries.push(quote! {
+                    { #(#normal,)* }
+                    (#macro_path)
+                    (#{protocol_header()})
+                    [#ident, (#ty), (#(if let Some(id_base) = id_base) { #id_base, }), #(#arg_tys),*]
+                });
+                normal.clear();
             }
         }
     }
-    (out, first_macro_path)
-}
-
-fn emit_macro_inner(variants: &[ParsedVariant], enum_decl: &TokenStream) -> TokenStream {
-    let (out, first_macro_path) = serialize_variants(variants, false);
-    if let Some(mac) = first_macro_path {
-        quote! {
-            #mac!(
-                @emit_enum
-                #mac
-                { #enum_decl }
-                []
-                #out
-            );
-        }
-    } else {
-        quote! {
-            #enum_decl {
-                #(for variant in variants) {
-                    #(if let ParsedVariant::Normal(variant) = variant) {
-                        #variant,
-                    }
+    let chunks: Vec<_> = entries.chunks(CHUNK).collect();
+    if let [entries] = &chunks[..] {
+        return quote! {
+            #krate::__next_part! {
+                ($) #{fresh()} {}
+                [#(#entries)* { #(#normal,)* } => { #krate::structured_impl } { #args }]
+            }
+        };
+    }
+    let mut out = TokenStream::new();
+    let mut parts = Vec::new();
+    for (i, entries) in chunks.iter().enumerate() {
+        let part = fresh();
+        let trailing = if i + 1 == chunks.len() {
+            &normal[..]
+        } else {
+            &[]
+        };
+        out.extend(quote! {
+            #krate::__next_part! { ($) #part {} [#(#entries)* { #(#trailing,)* }] }
+        });
+        parts.push(part);
+    }
+    while parts.len() > 1 {
+        let mut merged = Vec::new();
+        for pair in parts.chunks(2) {
+            match pair {
+                [left, right] => {
+                    let part = fresh();
+                    out.extend(quote! { #left!(@merge ($) #right #part); });
+                    merged.push(part);
                 }
+                _ => merged.push(pair[0].clone()),
             }
         }
+        parts = merged;
+    }
+    quote! {
+        #out
+        #{&parts[0]}!(@finish { #krate::structured_impl } { #args });
     }
 }
 
-fn emit_macro(ident: &Ident, variants: &[ParsedVariant]) -> TokenStream {
-    let random_module_ident = Ident::new(
-        &format!("flat_enum_module_{:x}_{}", getrandom(), ident.to_string()),
-        Span::call_site(),
-    );
-    let random_macro_ident = Ident::new(
-        &format!(
-            "flat_enum_macro_{:x}_{}",
-            getrandom(),
-            ident
-        ),
-        Span::call_site(),
-    );
-    let inner = emit_macro_inner(variants, &quote! { $($enum_decl)* });
-    quote! {
-        #[allow(non_snake_case)]
-        mod #random_module_ident {
-            #[macro_export]
-            macro_rules! #random_macro_ident {
-                (@emit_enum
-                    flat_enum = #{env!("CARGO_PKG_VERSION")},
-                    enum_decl = { $($enum_decl:tt)* },
-                ) => { #inner };
-            }
-            #[allow(unused)]
-            pub use #random_macro_ident as #ident;
-        }
-        #[allow(unused)]
-        use #random_module_ident::*;
+/// Arguments of `structured_impl!`, which are the path of the crate, the flat enum, whether the
+/// helper macro is local, the variants of the structured enum in brackets and the structured enum,
+/// separated with `;`, followed by the variants of the flat enum in braces.
+pub struct StructuredArg {
+    krate: Path,
+    flat_path: Path,
+    local: bool,
+    structured_variants: Vec<StructuredVariant>,
+    input: ItemEnum,
+    variants: Vec<Variant>,
+}
+
+impl Parse for StructuredArg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let krate = input.parse()?;
+        input.parse::<Token![;]>()?;
+        let flat_path = input.parse()?;
+        input.parse::<Token![;]>()?;
+        let local: LitBool = input.parse()?;
+        input.parse::<Token![;]>()?;
+        let content;
+        bracketed!(content in input);
+        let structured_variants =
+            Punctuated::<StructuredVariant, Token![,]>::parse_terminated(&content)?
+                .into_iter()
+                .collect();
+        input.parse::<Token![;]>()?;
+        let item = input.parse()?;
+        let content;
+        braced!(content in input);
+        let variants = Punctuated::<Variant, Token![,]>::parse_terminated(&content)?
+            .into_iter()
+            .collect();
+        Ok(Self {
+            krate,
+            flat_path,
+            local: local.value,
+            structured_variants,
+            input: item,
+            variants,
+        })
     }
 }
 
-fn emit_into_flat(ident_flat: &Ident, variants: &[ParsedVariant]) -> TokenStream {
+/// Emits `IntoFlat`, which converts each variant of the flat enum from the pattern of the
+/// structured enum given by the origin.
+fn emit_into_flat(
+    input: &ItemEnum,
+    krate: &Path,
+    flat_path: &Path,
+    structured_variants: &[StructuredVariant],
+    variants: &[Variant],
+    origins: &[Origin],
+) -> TokenStream {
+    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
+    let this: Path = parse_quote!(Self);
+    let flat = path_without_arguments(flat_path);
+    let arms: Vec<_> = variants
+        .iter()
+        .zip(origins)
+        .map(|(variant, origin)| {
+            let ids = field_ids(variant, "a");
+            (
+                cmp::emit_structured_pattern(&this, variant, origin, &ids),
+                emit_pattern(&quote!(#flat), variant, &ids),
+            )
+        })
+        .collect();
+    // The `#[flatten]` variants of the enums without variants
+    let empty: Vec<_> = structured_variants
+        .iter()
+        .filter(|sv| {
+            sv.flatten
+                && !origins
+                    .iter()
+                    .any(|origin| origin.groups.first() == Some(&sv.ident))
+        })
+        .map(|sv| &sv.ident)
+        .collect();
     quote! {
-        #(for variant in variants) {
-            #(if let ParsedVariant::Normal(variant) = variant) {
-                Self :: #{ &variant.ident }
-                #(if let Fields::Named(fields) = &variant.fields) {
-                    {
-                        #(for field in &fields.named) {
-                            #{&field.ident}
-                        }
-                    } => #ident_flat :: #{ &variant.ident } {
-                        #(for field in &fields.named) {
-                            #{&field.ident}
-                        }
-                    }
+        #[automatically_derived]
+        unsafe impl #g_impl #krate::IntoFlat for #{&input.ident} #g_type #g_where {
+            type Flat = #flat_path;
+            fn into_flat(self) -> Self::Flat {
+                #(if input.variants.is_empty()) {
+                    match self {}
                 }
-                #(if let Fields::Unnamed(fields) = &variant.fields) {
-                    #(let ids = (0..fields.unnamed.len()).map(|i| Ident::new(&format!("a{}", i), Span::call_site())).collect::<Vec<_>>()){
-                        ( #(#ids),* ) => #ident_flat :: #{ &variant.ident } (#(#ids),*)
+                #(else) {
+                    match self {
+                        #(for (structured, flat) in &arms) { #structured => #flat, }
+                        #(for ident in &empty) { Self::#ident(inner) => match inner {}, }
                     }
                 }
-                #(if let Fields::Unit = &variant.fields) {
-                    => #ident_flat:: #{ &variant.ident }
-                }
             }
-            #(if let ParsedVariant::Flattened{ident, macro_path, ..} = variant) {
-                Self :: #ident (item) => {
-                    #macro_path ! (@emit_flat item, (#macro_path), #ident_flat)
+
+            fn from_flat(this: Self::Flat) -> Self {
+                #(if variants.is_empty()) {
+                    match this {}
                 }
-            },
+                #(else) {
+                    match this {
+                        #(for (structured, flat) in &arms) { #flat => #structured, }
+                    }
+                }
+            }
         }
     }
 }
 
-fn emit_from_flat(ident_flat: &Ident, variants: &[ParsedVariant]) -> TokenStream {
-    let (out, first_macro_path) = serialize_variants(variants, true);
-    if let Some(mac) = first_macro_path {
-        quote! {
-            #mac!(
-                @emit_unflat #mac []
-                (this, #ident_flat, Self)
-                #out
-            );
+/// Called on the structured enum with the variants of the flat enum, which are collected from the
+/// helper macros of the flattened enums. Emits the helper macro of the structured enum, which
+/// fills the flat enum with the variants, and `IntoFlat`.
+///
+/// The field types of the flat enum are written as `<Enum2<A> as LeakFlat<N>>::Ty` with the
+/// structured enum, so that `#[flat]` needs only the path of the structured enum.
+pub fn structured_impl(
+    StructuredArg {
+        krate,
+        flat_path,
+        local,
+        structured_variants,
+        input,
+        variants,
+    }: StructuredArg,
+) -> TokenStream {
+    // The field types as written are recorded on the variants, which decide the shapes in `map(..)`.
+    let variants: Vec<_> = variants
+        .into_iter()
+        .map(|mut variant| {
+            if Origin::find(&variant).fields.is_none() {
+                let origin = Origin::take(&mut variant);
+                let fields = origin.field_types_or(&variant);
+                let id = origin.id.iter();
+                variant
+                    .attrs
+                    .push(parse_quote!(#[flat_enum(fields(#(#fields),*) #(, id = #id)*)]));
+            }
+            variant
+        })
+        .collect();
+    let origins: Vec<_> = variants.iter().map(Origin::find).collect();
+    let mut leak_types: Vec<&Type> = Vec::new();
+    for field in variants.iter().flat_map(|variant| &variant.fields) {
+        if !leak_types.contains(&&field.ty) {
+            leak_types.push(&field.ty);
         }
-    } else {
+    }
+    let leak_index = |ty: &Type| leak_types.iter().position(|t| *t == ty).unwrap();
+    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
+    let krate_in_macro = path_in_macro(&krate);
+    let discriminant = LitBool::new(origin::has_custom_discriminants(&input), Span::call_site());
+    let rules = |header: &TokenStream| {
         quote! {
-            match this {
-                #(for variant in variants) {
-                    #(if let ParsedVariant::Normal(variant) = variant) {
-                        #ident_flat :: #{ &variant.ident }
-                        #(if let Fields::Named(fields) = &variant.fields) {
-                            {
-                                #(for field in &fields.named) {
-                                    #{&field.ident}
-                                }
-                            } => Self :: #{ &variant.ident } {
-                                #(for field in &fields.named) {
-                                    #{&field.ident}
-                                }
-                            }
-                        }
-                        #(if let Fields::Unnamed(fields) = &variant.fields) {
-                            #(let ids = (0..fields.unnamed.len()).map(|i| Ident::new(&format!("a{}", i), Span::call_site())).collect::<Vec<_>>()){
-                                ( #(#ids),* ) => Self :: #{ &variant.ident } (#(#ids),*)
-                            }
-                        }
-                        #(if let Fields::Unit = &variant.fields) {
-                            => Self:: #{ &variant.ident }
-                        }
-                    },
+            (@emit_enum
+                #header
+                structured = { $($structured:tt)* },
+                args = { $($args:tt)* },
+                enum_decl = { $($enum_decl:tt)* },
+            ) => {
+                #[#krate_in_macro::flat_impl(#krate_in_macro; [#(#structured_variants),*]; #discriminant; $($args)*)]
+                $($enum_decl)* {
+                    #(for variant in &variants) {
+                        #{emit_variant(variant, |ty| quote! {
+                            <$($structured)* as #krate_in_macro::LeakFlat<{#{leak_index(ty)}}>>::Ty
+                        })},
+                    }
                 }
+            };
+        }
+    };
+    quote! {
+        #(for (n, ty) in leak_types.iter().enumerate()) {
+            #[automatically_derived]
+            unsafe impl #g_impl #krate::LeakFlat<{#n}> for #{&input.ident} #g_type #g_where {
+                type Ty = #ty;
             }
         }
+        #{emit_helper_macro(&input.ident, &input.vis, &input, rules, local)}
+        #{emit_into_flat(&input, &krate, &flat_path, &structured_variants, &variants, &origins)}
     }
 }
 
-#[derive(Parse)]
 pub struct MacroArg {
     flat_path: Path,
-    _at_token: Option<Token![@]>,
-    #[parse_if(_at_token.is_some())]
     krate: Option<Path>,
+    map: Option<Ident>,
+    codec: bool,
+    local: bool,
+}
+
+impl Parse for MacroArg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let flat_path = input.parse()?;
+        let krate = if input.parse::<Option<Token![@]>>()?.is_some() {
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        let mut map = None;
+        let mut codec = false;
+        let mut local = false;
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let ident: Ident = input.parse()?;
+            match ident.to_string().as_str() {
+                "map" => {
+                    let content;
+                    parenthesized!(content in input);
+                    map = Some(content.parse()?);
+                }
+                "codec" => codec = true,
+                "local" => local = true,
+                _ => return Err(Error::new(ident.span(), "Unknown option for #[into_flat]")),
+            }
+        }
+        Ok(Self {
+            flat_path,
+            krate,
+            map,
+            codec,
+            local,
+        })
+    }
 }
 
 pub fn into_flat(arg: MacroArg, mut input: ItemEnum) -> TokenStream {
     let MacroArg {
-        flat_path, krate, ..
+        flat_path,
+        krate,
+        map: map_param,
+        codec,
+        local,
     } = arg;
-    let krate = krate.unwrap_or(parse_quote!(::flat_enum));
-    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
-    let flat_name = if flat_path.leading_colon.is_none() && flat_path.segments.len() == 1 {
-        &flat_path.segments[0].ident
-    } else {
-        abort!(flat_path.span(), "Should be exist in the same context");
-    };
+    let options = DeriveOptions::from_attrs(&input.attrs);
+    input.attrs.retain(|attr| !attr.path.is_ident("flat_enum"));
+    let krate = krate
+        .or(options.krate)
+        .unwrap_or_else(|| parse_quote!(::flat_enum));
+    let map_param = map_param.or(options.map);
+    let codec = codec || options.codec;
+    let local = local || options.local;
     let variants: Vec<_> = input
         .variants
         .iter()
         .cloned()
         .map(ParsedVariant::new)
         .collect();
-    let mac_def = emit_macro(&input.ident, &variants[..]);
-    let fn_into_flat = emit_into_flat(&flat_name, &variants[..]);
-    let fn_from_flat = emit_from_flat(&flat_name, &variants[..]);
+    let structured_variants: Vec<_> = variants
+        .iter()
+        .map(|variant| match variant {
+            ParsedVariant::Normal(variant) => StructuredVariant {
+                ident: variant.ident.clone(),
+                flatten: false,
+            },
+            ParsedVariant::Flattened { ident, .. } => StructuredVariant {
+                ident: ident.clone(),
+                flatten: true,
+            },
+        })
+        .collect();
+    let flattens: Vec<_> = variants
+        .iter()
+        .map(|variant| match variant {
+            ParsedVariant::Flattened { ty, .. } => Some(ty.clone()),
+            ParsedVariant::Normal(_) => None,
+        })
+        .collect();
+    let ids: Vec<_> = variants
+        .iter()
+        .map(|variant| match variant {
+            ParsedVariant::Flattened { id_base, .. } => id_base.clone(),
+            ParsedVariant::Normal(variant) => Origin::find(variant).id,
+        })
+        .collect();
     input.variants.iter_mut().for_each(|variant| {
         let attrs = variant.attrs.clone();
         variant.attrs = attrs
             .into_iter()
-            .filter(|attr| !attr.path.is_ident("flatten"))
+            .filter(|attr| !attr.path.is_ident("flatten") && !attr.path.is_ident("flat_enum"))
             .collect();
     });
+    // Without `#[flatten]` variants, the variants are known here.
+    let structured = if flattens.iter().any(Option::is_some) {
+        emit_parts(
+            &input,
+            &variants,
+            &krate,
+            &quote! {
+                #krate; #flat_path; #{LitBool::new(local, Span::call_site())};
+                [#(#structured_variants),*]; #input
+            },
+        )
+    } else {
+        structured_impl(StructuredArg {
+            krate: krate.clone(),
+            flat_path,
+            local,
+            structured_variants,
+            input: input.clone(),
+            variants: variants
+                .into_iter()
+                .map(|variant| match variant {
+                    ParsedVariant::Normal(variant) => variant,
+                    ParsedVariant::Flattened { .. } => unreachable!(),
+                })
+                .collect(),
+        })
+    };
     quote! {
         #input
-        #mac_def
-        #[automatically_derived]
-        unsafe impl #g_impl #krate::IntoFlat for #{&input.ident} #g_type #g_where {
-            type Flat = #flat_path;
-            fn into_flat(self) -> Self::Flat {
-                match self {
-                    #fn_into_flat
-                }
-            }
-
-            fn from_flat(this: Self::Flat) -> Self {
-                #fn_from_flat
-            }
+        #structured
+        #{variant_info::emit(&input, &krate, None)}
+        #{descriptor::emit(&input, &krate, "Structured", &flattens, &ids)}
+        #(if let Some(param) = &map_param) {
+            #{map::emit(&input, &krate, param, &map::field_shapes(&input, &vec![Origin::default(); input.variants.len()]))}
+        }
+        #(if codec) {
+            #{codec::emit(&input, &krate)}
         }
     }
 }
@@ -0,0 +1,278 @@
+use crate::origin::Origin;
+use crate::util::{
+    add_bounds, emit_pattern, field_ids, field_types, path_without_arguments, type_to_enum_path,
+};
+use proc_macro2::{Literal, TokenStream};
+use proc_macro_error::abort;
+use syn::*;
+use template_quote::quote;
+
+/// Traits which can be implemented consistently with the structured enum.
+pub const TRAITS: &[&str] = &["PartialEq", "Eq", "PartialOrd", "Ord", "Hash"];
+
+/// Emits the pattern of the structured enum `structured` matching the value converted from
+/// `variant`, binding the fields to `ids`.
+pub fn emit_structured_pattern(
+    structured: &Path,
+    variant: &Variant,
+    origin: &Origin,
+    ids: &[Ident],
+) -> TokenStream {
+    match (origin.groups.first(), &origin.ty) {
+        (Some(group), Some(ty)) => {
+            let inner = emit_pattern(&quote!(#{type_to_enum_path(ty)}), variant, ids);
+            quote! { #structured :: #group ( #inner ) }
+        }
+        _ => emit_pattern(&quote!(#structured), variant, ids),
+    }
+}
+
+/// Emits the comparison of the fields `a` and `b` with `method` of `tr`, which returns the first
+/// result other than `equal`, like the derived impls.
+fn emit_chain(
+    tr: &TokenStream,
+    method: &TokenStream,
+    equal: &TokenStream,
+    a: &[Ident],
+    b: &[Ident],
+) -> TokenStream {
+    match (a.split_last(), b.split_last()) {
+        (Some((a_last, a)), Some((b_last, b))) => {
+            let mut out = quote! { #tr::#method(#a_last, #b_last) };
+            for (a, b) in a.iter().zip(b).rev() {
+                out = quote! {
+                    match #tr::#method(#a, #b) {
+                        #equal => #out,
+                        cmp => cmp,
+                    }
+                };
+            }
+            out
+        }
+        _ => equal.clone(),
+    }
+}
+
+/// Emits the closure returning the indices of the variants in the enums which the variant of the
+/// flat enum goes through.
+fn emit_discr(input: &ItemEnum, origins: &[Origin]) -> TokenStream {
+    quote! {
+        |value: &Self| -> &'static [usize] {
+            match value {
+                #(for (variant, origin) in input.variants.iter().zip(origins)) {
+                    Self::#{&variant.ident} { .. } => &[
+                        #(for level in &origin.levels) { #{Literal::usize_unsuffixed(level.index)}, }
+                    ],
+                }
+            }
+        }
+    }
+}
+
+/// Implements the traits in `traits` on the flat enum, which behave like the traits derived on
+/// the structured enum. The values are compared and hashed by the indices of the variants in the
+/// enums in `Origin::levels`, and then by the fields.
+pub fn emit(
+    input: &ItemEnum,
+    structured: &Path,
+    origins: &[Origin],
+    traits: &[Ident],
+) -> TokenStream {
+    // The derived impls compare and hash the discriminants, which follow the indices only without
+    // explicit discriminants and `#[repr(..)]`.
+    for tr in traits.iter().filter(|tr| *tr != "PartialEq" && *tr != "Eq") {
+        if let Some(level) = origins
+            .iter()
+            .flat_map(|origin| &origin.levels)
+            .find(|level| level.discriminant)
+        {
+            abort!(
+                tr.span(),
+                "`structured({})` cannot agree with the derived impl of `{}`, which has explicit discriminants or `#[repr(..)]`",
+                tr,
+                level.name;
+                help = "Remove the discriminants and `#[repr(..)]`, or only use `PartialEq` and `Eq`"
+            );
+        }
+    }
+    let ident = &input.ident;
+    let g_type = input.generics.split_for_impl().1;
+    let bounds =
+        |tr: TokenStream| add_bounds(&input.generics, field_types(input), &parse_quote!(#tr));
+    let structured_pat = path_without_arguments(structured);
+    let variants: Vec<_> = input
+        .variants
+        .iter()
+        .zip(origins)
+        .map(|(variant, origin)| {
+            (
+                variant,
+                origin,
+                field_ids(variant, "a"),
+                field_ids(variant, "b"),
+            )
+        })
+        .collect();
+    let empty = input.variants.is_empty();
+    let mut out = TokenStream::new();
+    for tr in traits {
+        out.extend(match tr.to_string().as_str() {
+            "PartialEq" => {
+                let generics = bounds(quote!(::core::cmp::PartialEq));
+                let (g_impl, _, g_where) = generics.split_for_impl();
+                quote! {
+                    #[automatically_derived]
+                    impl #g_impl ::core::cmp::PartialEq for #ident #g_type #g_where {
+                        fn eq(&self, other: &Self) -> bool {
+                            #(if empty) {
+                                match *self {}
+                            }
+                            #(else) {
+                                #[allow(unreachable_patterns)]
+                                match (self, other) {
+                                    #(for (variant, _, a, b) in &variants) {
+                                        (
+                                            #{emit_pattern(&quote!(Self), variant, a)},
+                                            #{emit_pattern(&quote!(Self), variant, b)},
+                                        ) => true #(for (a, b) in a.iter().zip(b)) { && #a == #b },
+                                    }
+                                    _ => false,
+                                }
+                            }
+                        }
+                    }
+
+                    #[automatically_derived]
+                    impl #g_impl ::core::cmp::PartialEq<#structured> for #ident #g_type #g_where {
+                        fn eq(&self, other: &#structured) -> bool {
+                            #(if empty) {
+                                match *self {}
+                            }
+                            #(else) {
+                                #[allow(unreachable_patterns)]
+                                match (self, other) {
+                                    #(for (variant, origin, a, b) in &variants) {
+                                        (
+                                            #{emit_pattern(&quote!(Self), variant, a)},
+                                            #{emit_structured_pattern(&structured_pat, variant, origin, b)},
+                                        ) => true #(for (a, b) in a.iter().zip(b)) { && #a == #b },
+                                    }
+                                    _ => false,
+                                }
+                            }
+                        }
+                    }
+
+                    #[automatically_derived]
+                    impl #g_impl ::core::cmp::PartialEq<#ident #g_type> for #structured #g_where {
+                        fn eq(&self, other: &#ident #g_type) -> bool {
+                            other == self
+                        }
+                    }
+                }
+            }
+            "Eq" => {
+                let generics = bounds(quote!(::core::cmp::Eq));
+                let (g_impl, _, g_where) = generics.split_for_impl();
+                quote! {
+                    #[automatically_derived]
+                    impl #g_impl ::core::cmp::Eq for #ident #g_type #g_where {}
+                }
+            }
+            "PartialOrd" => {
+                let generics = bounds(quote!(::core::cmp::PartialOrd));
+                let (g_impl, _, g_where) = generics.split_for_impl();
+                let tr = quote!(::core::cmp::PartialOrd);
+                let equal = quote!(::core::option::Option::Some(::core::cmp::Ordering::Equal));
+                quote! {
+                    #[automatically_derived]
+                    impl #g_impl ::core::cmp::PartialOrd for #ident #g_type #g_where {
+                        fn partial_cmp(&self, other: &Self) -> ::core::option::Option<::core::cmp::Ordering> {
+                            #(if empty) {
+                                match *self {}
+                            }
+                            #(else) {
+                                let discr = #{emit_discr(input, origins)};
+                                #[allow(unreachable_patterns)]
+                                match (self, other) {
+                                    #(for (variant, _, a, b) in &variants) {
+                                        (
+                                            #{emit_pattern(&quote!(Self), variant, a)},
+                                            #{emit_pattern(&quote!(Self), variant, b)},
+                                        ) => #{emit_chain(&tr, &quote!(partial_cmp), &equal, a, b)},
+                                    }
+                                    _ => ::core::cmp::PartialOrd::partial_cmp(discr(self), discr(other)),
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            "Ord" => {
+                let generics = bounds(quote!(::core::cmp::Ord));
+                let (g_impl, _, g_where) = generics.split_for_impl();
+                let tr = quote!(::core::cmp::Ord);
+                let equal = quote!(::core::cmp::Ordering::Equal);
+                quote! {
+                    #[automatically_derived]
+                    impl #g_impl ::core::cmp::Ord for #ident #g_type #g_where {
+                        fn cmp(&self, other: &Self) -> ::core::cmp::Ordering {
+                            #(if empty) {
+                                match *self {}
+                            }
+                            #(else) {
+                                let discr = #{emit_discr(input, origins)};
+                                #[allow(unreachable_patterns)]
+                                match (self, other) {
+                                    #(for (variant, _, a, b) in &variants) {
+                                        (
+                                            #{emit_pattern(&quote!(Self), variant, a)},
+                                            #{emit_pattern(&quote!(Self), variant, b)},
+                                        ) => #{emit_chain(&tr, &quote!(cmp), &equal, a, b)},
+                                    }
+                                    _ => ::core::cmp::Ord::cmp(discr(self), discr(other)),
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            "Hash" => {
+                let generics = bounds(quote!(::core::hash::Hash));
+                let (g_impl, _, g_where) = generics.split_for_impl();
+                quote! {
+                    #[automatically_derived]
+                    impl #g_impl ::core::hash::Hash for #ident #g_type #g_where {
+                        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+                            #(if empty) {
+                                let _ = state;
+                                match *self {}
+                            }
+                            #(else) {
+                                match self {
+                                    #(for (variant, origin, a, _) in &variants) {
+                                        #{emit_pattern(&quote!(Self), variant, a)} => {
+                                            // The derived impls hash the discriminant as `isize`,
+                                            // except on the enums with a single variant.
+                                            #(for level in origin.levels.iter().filter(|level| level.count > 1)) {
+                                                ::core::hash::Hash::hash(
+                                                    &#{Literal::isize_suffixed(level.index as isize)},
+                                                    state,
+                                                );
+                                            }
+                                            #(for a in a) {
+                                                ::core::hash::Hash::hash(#a, state);
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            _ => unreachable!(),
+        });
+    }
+    out
+}
@@ -0,0 +1,76 @@
+use crate::util::{emit_pattern, field_ids};
+use proc_macro2::TokenStream;
+use syn::*;
+use template_quote::quote;
+
+/// Implements `codec::Encode` and `codec::Decode`, which write the index of the variant as the
+/// tag followed by the fields. On the flat enum, it is the only tag in the value.
+pub fn emit(input: &ItemEnum, krate: &Path) -> TokenStream {
+    let ident = &input.ident;
+    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
+    let mut field_types: Vec<&Type> = Vec::new();
+    for field in input.variants.iter().flat_map(|variant| &variant.fields) {
+        if !field_types.contains(&&field.ty) {
+            field_types.push(&field.ty);
+        }
+    }
+    let where_clause = |tr: TokenStream| -> WhereClause {
+        let mut where_clause = g_where.cloned().unwrap_or_else(|| parse_quote!(where));
+        for ty in &field_types {
+            where_clause.predicates.push(parse_quote!(#ty: #tr));
+        }
+        where_clause
+    };
+    let codec = quote!(#krate::codec);
+    quote! {
+        #[automatically_derived]
+        impl #g_impl #codec::Encode for #ident #g_type
+        #{where_clause(quote!(#codec::Encode))}
+        {
+            fn encode(&self, out: &mut ::std::vec::Vec<u8>) {
+                #(if input.variants.is_empty()) {
+                    let _ = out;
+                    match *self {}
+                }
+                #(else) {
+                    match self {
+                        #(for (tag, variant) in input.variants.iter().enumerate()) {
+                            #(let ids = field_ids(variant, "a")) {
+                                #{emit_pattern(&quote!(Self), variant, &ids)} => {
+                                    #codec::write_tag(out, #{tag as u64});
+                                    #(for id in &ids) {
+                                        #codec::Encode::encode(#id, out);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        #[automatically_derived]
+        impl #g_impl #codec::Decode for #ident #g_type
+        #{where_clause(quote!(#codec::Decode))}
+        {
+            fn decode(input: &mut &[u8]) -> ::core::result::Result<Self, #codec::DecodeError> {
+                ::core::result::Result::Ok(match #codec::read_tag(input)? {
+                    #(for (tag, variant) in input.variants.iter().enumerate()) {
+                        #{tag as u64} => Self::#{&variant.ident}
+                        #(if let Fields::Named(fields) = &variant.fields) {
+                            {
+                                #(for field in &fields.named), {
+                                    #{&field.ident}: #codec::Decode::decode(input)?
+                                }
+                            }
+                        }
+                        #(if let Fields::Unnamed(fields) = &variant.fields) {
+                            (#(for _ in &fields.unnamed), { #codec::Decode::decode(input)? })
+                        },
+                    }
+                    tag => return ::core::result::Result::Err(#codec::DecodeError::InvalidTag(tag)),
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::util::tokens_to_string;
+use proc_macro2::TokenStream;
+use proc_macro_error::abort;
+use syn::parse::{Parse, ParseStream};
+use syn::*;
+use template_quote::quote;
+
+/// `name: Ty` or `name = index: Ty` in `common(..)`.
+pub struct CommonField {
+    name: Ident,
+    index: Option<LitInt>,
+    ty: Type,
+}
+
+impl Parse for CommonField {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let name = input.parse()?;
+        let index = if input.parse::<Option<Token![=]>>()?.is_some() {
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        input.parse::<Token![:]>()?;
+        Ok(Self {
+            name,
+            index,
+            ty: input.parse()?,
+        })
+    }
+}
+
+impl CommonField {
+    /// Returns the member of the field in `variant`.
+    fn member(&self, variant: &Variant) -> Option<Member> {
+        match &variant.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .any(|field| field.ident.as_ref() == Some(&self.name))
+                .then(|| Member::Named(self.name.clone())),
+            Fields::Unnamed(fields) => {
+                let index: usize = self.index.as_ref()?.base10_parse().ok()?;
+                (index < fields.unnamed.len()).then(|| {
+                    Member::Unnamed(Index {
+                        index: index as u32,
+                        span: self.name.span(),
+                    })
+                })
+            }
+            Fields::Unit => None,
+        }
+    }
+}
+
+/// Emits the accessors of the field which all variants have.
+pub fn emit(input: &ItemEnum, common: &CommonField) -> TokenStream {
+    let members: Vec<_> = input
+        .variants
+        .iter()
+        .map(|variant| common.member(variant))
+        .collect();
+    let missing: Vec<_> = input
+        .variants
+        .iter()
+        .zip(&members)
+        .filter(|(_, member)| member.is_none())
+        .map(|(variant, _)| variant.ident.to_string())
+        .collect();
+    if !missing.is_empty() {
+        abort!(
+            common.name.span(),
+            "The field `{}` is missing in the variants: {}",
+            common.name,
+            missing.join(", ");
+            help = "Named fields are found by the name, and tuple fields by the index like `common({} = 0: {})`",
+            common.name,
+            tokens_to_string(quote!(#{&common.ty}))
+        );
+    }
+    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
+    let name = &common.name;
+    let name_mut = Ident::new(&format!("{}_mut", name), name.span());
+    let ty = &common.ty;
+    let arms = quote! {
+        #(for (variant, member) in input.variants.iter().zip(&members)) {
+            Self::#{&variant.ident} { #{member.as_ref().unwrap()}: field, .. } => field,
+        }
+    };
+    quote! {
+        #[automatically_derived]
+        impl #g_impl #{&input.ident} #g_type #g_where {
+            #[doc = #{format!("Returns the reference to `{}`, which all variants have.", name)}]
+            #{&input.vis} fn #name(&self) -> &#ty {
+                #(if input.variants.is_empty()) { match *self {} }
+                #(else) { match self { #arms } }
+            }
+
+            #[doc = #{format!("Returns the mutable reference to `{}`, which all variants have.", name)}]
+            #{&input.vis} fn #name_mut(&mut self) -> &mut #ty {
+                #(if input.variants.is_empty()) { match *self {} }
+                #(else) { match self { #arms } }
+            }
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::util::{
+    add_bounds, emit_helper_macro, field_types, generics_remove_defaults, path_in_macro,
+    protocol_header,
+};
+use proc_macro2::{Span, TokenStream, TokenTree};
+use proc_macro_error::abort;
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::*;
+use template_quote::quote;
+
+fn mentions_self(tokens: TokenStream) -> bool {
+    tokens.into_iter().any(|tt| match tt {
+        TokenTree::Ident(ident) => ident == "Self",
+        TokenTree::Group(g) => mentions_self(g.stream()),
+        _ => false,
+    })
+}
+
+/// Checks that every item of the trait can be delegated to the payloads.
+fn check_trait(input: &ItemTrait) {
+    for item in &input.items {
+        match item {
+            TraitItem::Method(method) => {
+                match method.sig.inputs.first() {
+                    Some(FnArg::Receiver(_)) => {
+                        // The payload returns or takes its own type, not the flat enum.
+                        let output = &method.sig.output;
+                        if mentions_self(quote!(#output)) {
+                            abort!(method.sig, "Methods returning `Self` cannot be delegated")
+                        }
+                        let args = method.sig.inputs.iter().skip(1);
+                        if args.clone().any(|arg| mentions_self(quote!(#arg))) {
+                            abort!(
+                                method.sig,
+                                "Methods taking `Self` in the arguments other than the receiver cannot be delegated"
+                            )
+                        }
+                    }
+                    Some(FnArg::Typed(arg)) if matches!(&*arg.pat, Pat::Ident(pat) if pat.ident == "self") =>
+                    {
+                        abort!(
+                            arg.span(),
+                            "Only `self`, `&self` or `&mut self` can be delegated"
+                        )
+                    }
+                    _ if method.default.is_some() => (),
+                    _ => abort!(
+                        method.sig.span(),
+                        "Methods without receivers should have the default implementation"
+                    ),
+                }
+                if method.sig.variadic.is_some() {
+                    abort!(
+                        method.sig.variadic.span(),
+                        "Variadic methods cannot be delegated"
+                    );
+                }
+            }
+            TraitItem::Const(item) if item.default.is_some() => (),
+            TraitItem::Macro(_) | TraitItem::Verbatim(_) => (),
+            _ => abort!(
+                item.span(),
+                "Associated items other than methods cannot be delegated"
+            ),
+        }
+    }
+}
+
+/// Emits the macro named after the trait, which passes the trait definition to
+/// `delegate_impl!`.
+pub fn delegatable(krate: Option<Path>, input: ItemTrait) -> TokenStream {
+    let krate = krate.unwrap_or_else(|| parse_quote!(::flat_enum));
+    check_trait(&input);
+    let ident = &input.ident;
+    let mut trait_decl = input.clone();
+    trait_decl.attrs.clear();
+    let krate = path_in_macro(&krate);
+    quote! {
+        #input
+        #{emit_helper_macro(ident, &input.vis, &input, |header| quote! {
+            (@emit_delegate
+                #header
+                trait_path = { $($trait_path:tt)* },
+                enum_decl = { $($enum_decl:tt)* },
+            ) => {
+                #krate::delegate_impl! {
+                    trait_decl = { #trait_decl },
+                    trait_path = { $($trait_path)* },
+                    enum_decl = { $($enum_decl)* },
+                }
+            };
+        }, false)}
+    }
+}
+
+fn braced_item<T: Parse>(input: ParseStream, name: &str) -> Result<T> {
+    let ident: Ident = input.parse()?;
+    if ident != name {
+        return Err(Error::new(ident.span(), format!("Expected `{}`", name)));
+    }
+    input.parse::<Token![=]>()?;
+    let content;
+    braced!(content in input);
+    let ret = content.parse()?;
+    input.parse::<Token![,]>()?;
+    Ok(ret)
+}
+
+/// Argument of `#[delegatable]`, which is `crate = path` or just the path of the crate.
+pub struct CratePath(pub Path);
+
+impl Parse for CratePath {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(Token![crate]) && input.peek2(Token![=]) {
+            input.parse::<Token![crate]>()?;
+            input.parse::<Token![=]>()?;
+        }
+        Ok(Self(input.parse()?))
+    }
+}
+
+pub struct DelegateArg {
+    trait_decl: ItemTrait,
+    trait_path: Path,
+    enum_decl: ItemEnum,
+}
+
+impl Parse for DelegateArg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        Ok(Self {
+            trait_decl: braced_item(input, "trait_decl")?,
+            trait_path: braced_item(input, "trait_path")?,
+            enum_decl: braced_item(input, "enum_decl")?,
+        })
+    }
+}
+
+fn emit_method(call_path: &TokenStream, input: &ItemEnum, method: &TraitItemMethod) -> TokenStream {
+    let sig = &method.sig;
+    let receiver = match sig.inputs.first() {
+        Some(FnArg::Receiver(receiver)) => receiver,
+        _ => return TokenStream::new(),
+    };
+    let ids: Vec<_> = (1..sig.inputs.len())
+        .map(|i| Ident::new(&format!("arg{}", i), Span::call_site()))
+        .collect();
+    let self_ = Ident::new("self", Span::call_site());
+    let mut inputs = vec![if let Some((_, lifetime)) = &receiver.reference {
+        quote!(&#lifetime #{&receiver.mutability} #self_)
+    } else {
+        quote!(#{&receiver.mutability} #self_)
+    }];
+    for (arg, id) in sig.inputs.iter().skip(1).zip(&ids) {
+        if let FnArg::Typed(arg) = arg {
+            inputs.push(quote!(#id: #{&arg.ty}));
+        }
+    }
+    let call = |inner: TokenStream| {
+        quote! {
+            #call_path::#{&sig.ident}(#inner #(for id in &ids) { , #id })
+            #(if sig.asyncness.is_some()) { .await }
+        }
+    };
+    quote! {
+        #[inline]
+        #{&sig.constness} #{&sig.asyncness} #{&sig.unsafety} #{&sig.abi}
+        fn #{&sig.ident} #{&sig.generics} (#(#inputs),*) #{&sig.output} #{&sig.generics.where_clause} {
+            #(if sig.unsafety.is_some()) {
+                unsafe { #{emit_match(input, &self_, receiver, call)} }
+            }
+            #(else) {
+                #{emit_match(input, &self_, receiver, call)}
+            }
+        }
+    }
+}
+
+fn emit_match(
+    input: &ItemEnum,
+    self_: &Ident,
+    receiver: &Receiver,
+    call: impl Fn(TokenStream) -> TokenStream,
+) -> TokenStream {
+    let inner = Ident::new("inner", Span::call_site());
+    quote! {
+        #(if input.variants.is_empty()) {
+            #(if receiver.reference.is_some()) {
+                match *#self_ {}
+            }
+            #(else) {
+                match #self_ {}
+            }
+        }
+        #(else) {
+            match #self_ {
+                #(for variant in &input.variants) {
+                    #(if let Fields::Named(fields) = &variant.fields) {
+                        Self::#{&variant.ident} { #{&fields.named[0].ident}: #inner }
+                    }
+                    #(else) {
+                        Self::#{&variant.ident}(#inner)
+                    }
+                    => #{call(quote!(#inner))},
+                }
+            }
+        }
+    }
+}
+
+/// Implements the trait on the flat enum, by forwarding each method to the payload.
+pub fn delegate_impl(arg: DelegateArg) -> TokenStream {
+    let DelegateArg {
+        trait_decl,
+        trait_path,
+        enum_decl: input,
+    } = arg;
+    let trait_generics = trait_decl.generics.split_for_impl().1;
+    let call_path = quote!(#trait_path #{trait_generics.as_turbofish()});
+    let trait_path = quote!(#trait_path #trait_generics);
+    let mut generics = input.generics.clone();
+    // Lifetimes should be declared before the other parameters.
+    let (lifetimes, others): (Vec<_>, Vec<_>) = input
+        .generics
+        .params
+        .iter()
+        .chain(&generics_remove_defaults(&trait_decl.generics).params)
+        .cloned()
+        .partition(|param| matches!(param, GenericParam::Lifetime(_)));
+    generics.params = lifetimes.into_iter().chain(others).collect();
+    if let Some(where_clause) = &trait_decl.generics.where_clause {
+        generics
+            .make_where_clause()
+            .predicates
+            .extend(where_clause.predicates.iter().cloned());
+    }
+    let generics = add_bounds(&generics, field_types(&input), &parse_quote!(#trait_path));
+    let (g_impl, _, g_where) = generics.split_for_impl();
+    let g_type = input.generics.split_for_impl().1;
+    quote! {
+        #[automatically_derived]
+        impl #g_impl #trait_path for #{&input.ident} #g_type #g_where {
+            #(for item in &trait_decl.items) {
+                #(if let TraitItem::Method(method) = item) {
+                    #{emit_method(&call_path, &input, method)}
+                }
+            }
+        }
+    }
+}
+
+/// Emits the call of the macro which is generated by `#[delegatable]` on the trait, after
+/// checking that every variant has exactly one field.
+pub fn emit(input: &ItemEnum, trait_path: &Path) -> TokenStream {
+    for variant in &input.variants {
+        if variant.fields.len() != 1 {
+            abort!(
+                trait_path.span(),
+                "Cannot delegate `{}`, because the variant `{}` has {} fields",
+                quote!(#trait_path),
+                variant.ident,
+                variant.fields.len();
+                help = "Every variant should have exactly one field to delegate a trait"
+            );
+        }
+    }
+    let mut decl = input.clone();
+    decl.attrs.clear();
+    for variant in &mut decl.variants {
+        variant.attrs.clear();
+    }
+    quote! {
+        #trait_path!(
+            @emit_delegate
+            #{protocol_header()}
+            trait_path = { #trait_path },
+            enum_decl = { #decl },
+        );
+    }
+}
@@ -1,194 +1,102 @@
+use crate::codec;
+use crate::descriptor;
+use crate::map;
+use crate::origin::{self, Origin, Param};
 use crate::util::{
-    generic_arg_to_type, generics_remove_defaults, generics_to_arguments, getrandom,
+    emit_helper_macro, generic_arg_to_type, generics_remove_defaults, generics_to_arguments,
+    path_in_macro, tokens_to_string,
 };
-use proc_macro2::{Span, TokenStream};
+use crate::variant_info;
+use crate::visitor;
+use proc_macro2::TokenStream;
 use proc_macro_error::abort;
 use std::collections::HashMap;
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
 use syn::*;
 use template_quote::quote;
 
+/// Emits the rules of the helper macro, which match `header` after the command.
 fn emit_macro(
     input: &ItemEnum,
-    macro_ident: &Ident,
+    header: &TokenStream,
     flat_enum: &Path,
     leak_dict: &HashMap<Type, usize>,
+    origins: &[Origin],
 ) -> TokenStream {
     use Fields::*;
-    quote! {
-        #[macro_export]
-        macro_rules! #macro_ident {
-            (@emit_flat $matcher:expr, ($($from:tt)*), $to:ident) => {
-                match $matcher {
-                    #(for variant in &input.variants) {
-                        $($from)* :: #{ &variant.ident }
-                        #(if let Fields::Named(fields) = &variant.fields) {
-                            {
-                                #(for field in &fields.named) {
-                                    #{&field.ident}
-                                }
-                            } => $to :: #{ &variant.ident } {
-                                #(for field in &fields.named) {
-                                    #{&field.ident}
-                                }
-                            }
-                        }
-                        #(if let Fields::Unnamed(fields) = &variant.fields) {
-                            #(let ids = (0..fields.unnamed.len()).map(|i| Ident::new(&format!("a{}", i), Span::call_site())).collect::<Vec<_>>()){
-                                ( #(#ids),* ) => $to :: #{ &variant.ident } (#(#ids),*)
-                            }
-                        }
-                        #(if let Fields::Unit = &variant.fields) {
-                            => $to :: #{ &variant.ident }
-                        },
+    let params: Vec<_> = input
+        .generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Lifetime(lt) => Param::Lifetime(lt.lifetime.clone()),
+            GenericParam::Type(tp) => Param::Ident(tp.ident.clone()),
+            GenericParam::Const(cp) => Param::Ident(cp.ident.clone()),
+        })
+        .collect();
+    let flat_enum = path_in_macro(flat_enum);
+    // The variants appended to the flat enum, with the attributes recording the origins and the
+    // documentation of the variants.
+    let variants = quote! {
+        #(for (variant, origin) in input.variants.iter().zip(origins)) {
+            #{ origin.to_attr(variant, &quote!($name), &quote!($typ), &params, &quote!(($($enum_type_params),*)), &quote!($($id_base)*)) }
+            #(for attr in variant.attrs.iter().filter(|attr| attr.path.is_ident("doc"))) { #attr }
+            #{ &variant.ident }
+            #(if let Named(fields) = &variant.fields) {
+                {
+                    #(for field in &fields.named) {
+                        #(for attrs in &field.attrs) { #{attrs} }
+                        #{&field.vis}
+                        #{field.ident.as_ref().unwrap()}
+                        #{field.colon_token.as_ref().unwrap()}
+                        <
+                            $typ
+                            as #flat_enum::Leak<
+                                {#{ leak_index(leak_dict, &field.ty) }},
+                                ($($enum_type_params,)*),
+                            >
+                        >::Ty,
                     }
                 }
-
-            };
-            (@emit_unflat $self:path [$($out:tt)*] ($input:ident, $($_:tt)*)) => {
-                (match $input { $($out)* })
-            };
-            (@emit_unflat $self:path [$($out:tt)*] ($($args:tt)*) ($mac:path)[$($marg:tt)*] $($m:tt)*) => {
-                $mac! (
-                    @emit_unflat $mac [ $($out)* ] ($($args)*) @[$($marg)*] $($m)*
-                );
-            };
-            (@emit_unflat $self:path [$($out:tt)*] ($input:ident, $from:ident, $($to:tt)*) @[$name:ident, ($($typ:tt)*), $($_:tt)*] $($m:tt)*) => {
-                $self! (
-                    @emit_unflat $self
-                    [
-                        $($out)*
-                        #(for variant in &input.variants) {
-                            $from :: #{ &variant.ident }
-                            #(if let Fields::Named(fields) = &variant.fields) {
-                                {
-                                    #(for field in &fields.named) {
-                                        #{&field.ident}
-                                    }
-                                } => {return $($to)* :: $name ($($typ)* :: #{ &variant.ident }{
-                                    #(for field in &fields.named) {
-                                        #{&field.ident}
-                                    }
-                                });}
-                            }
-                            #(if let Fields::Unnamed(fields) = &variant.fields) {
-                                #(let ids = (0..fields.unnamed.len()).map(|i| Ident::new(&format!("a{}", i), Span::call_site())).collect::<Vec<_>>()){
-                                    ( #(#ids),* ) => {return $($to)* :: $name (
-                                            <$($typ)*> :: #{ &variant.ident }(#(#ids),*)
-                                    );}
-                                }
-                            }
-                            #(if let Fields::Unit = &variant.fields) {
-                                => {return $($to)* :: $name (<$($typ)*> :: #{ &variant.ident });}
-                            }
-                        }
-                    ]
-                    ($input, $from, $($to)*) $($m)*
+            }
+            #(if let Unnamed(fields) = &variant.fields) {
+                (
+                    #(for field in &fields.unnamed), {
+                        #(for attrs in &field.attrs) { #{attrs} }
+                        #{&field.vis}
+                        <
+                            $typ
+                            as #flat_enum::Leak<
+                                {#{ leak_index(leak_dict, &field.ty) }},
+                                ($($enum_type_params,)*),
+                            >
+                        >::Ty
+                    }
                 )
-            };
-            (@emit_unflat $self:path [ $($out:tt)* ] ($input:ident, $from:ident, $($to:tt)*) {$($raw:tt)*} $($m:tt)*) => {
-                $self! (@emit_unflat $self [
-                    $($out)*
-                    $from :: $($raw)* => {return $($to)* :: $($raw)*;}
-                ] ($input, $from, $($to)*) $($m)* );
-            };
-            (@emit_enum $self:path { $($enum_decl:tt)* } [ $($out:tt)* ]) => {
-                $($enum_decl)* { $($out)* }
-            };
-            (@emit_enum $self:path { $($enum_decl:tt)* } [ $($out:tt)* ] { $($raw:tt)* } $($t:tt)*) => {
-                $self!(@emit_enum $self {$($enum_decl)*} [$($out)* $($raw)*,]  $($t)*);
-            };
-            (@emit_enum $self:path { $($enum_decl:tt)* } [ $($out:tt)* ] ($mac:path) [$($marg:tt)*] $($t:tt)*) => {
-                $mac!(@emit_enum $mac {$($enum_decl)*} [$($out)*] @[$($marg)*] $($t)*);
-            };
-            (@emit_enum $self:path { $($enum_decl:tt)* } [ $($out:tt)* ] @[$_:ident, ($typ:ty), $($enum_type_params:ty),* $(,)?] $($t:tt)*) => {
-                $self!(
-                    @emit_enum $self { $($enum_decl)* } [
-                        $($out)*
-                        #(for variant in &input.variants) {
-                            #{ &variant.ident }
-                            #(if let Named(fields) = &variant.fields) {
-                                {
-                                    #(for field in &fields.named) {
-                                        #(for attrs in &field.attrs) { #{attrs} }
-                                        #{&field.vis}
-                                        #{field.ident.as_ref().unwrap()}
-                                        #{field.colon_token.as_ref().unwrap()}
-                                        <
-                                            $typ
-                                            as #flat_enum::Leak<
-                                                ($($enum_type_params,)*),
-                                                #{ leak_dict.get(&field.ty).unwrap() }
-                                            >
-                                        >::Ty,
-                                    }
-                                }
-                            }
-                            #(if let Unnamed(fields) = &variant.fields) {
-                                (
-                                    #(for field in &fields.unnamed), {
-                                        #(for attrs in &field.attrs) { #{attrs} }
-                                        #{&field.vis}
-                                        <
-                                            $typ
-                                            as #flat_enum::Leak<
-                                                {#{ *leak_dict.get(&field.ty).unwrap() }},
-                                                ($($enum_type_params,)*),
-                                            >
-                                        >::Ty
-                                    }
-                                )
-                            },
-                        }
-                    ]
-                    $($t)*
-                );
-            };
+            },
         }
-    }
-}
-
-fn emit_macro_export_in_macro_namespace(
-    input: &ItemEnum,
-    flat_enum: &Path,
-    leak_dict: &HashMap<Type, usize>,
-) -> TokenStream {
-    let random_module_ident = Ident::new(
-        &format!(
-            "flat_enum_module_{:x}_{}",
-            getrandom(),
-            input.ident.to_string()
-        ),
-        Span::call_site(),
-    );
-    let random_macro_ident = Ident::new(
-        &format!(
-            "flat_enum_macro_{:x}_{}",
-            getrandom(),
-            input.ident.to_string()
-        ),
-        Span::call_site(),
-    );
+    };
+    // The variants are added to the ones collected so far, and passed on to the next helper macro
+    // in `$rest` by `__next_part!`.
     quote! {
-        #[allow(non_snake_case)]
-        mod #random_module_ident {
-            #{emit_macro(input, &random_macro_ident, flat_enum, leak_dict)}
-            pub use #random_macro_ident as #{ &input.ident };
-        }
-        #{ &input.vis } use #random_module_ident::*;
+        (@emit_part #header ($d:tt) $part:ident [$name:ident, ($typ:ty), ($($id_base:tt)*), $($enum_type_params:ty),* $(,)?] { $($collected:tt)* } [$($rest:tt)*]) => {
+            #flat_enum::__next_part! { ($d) $part { $($collected)* #variants } [$($rest)*] }
+        };
     }
 }
 
 fn emit_impl(input: &ItemEnum, flat_enum: &Path, leak_dict: &HashMap<Type, usize>) -> TokenStream {
     let generic_impl = generics_remove_defaults(&input.generics);
     let arg = generics_to_arguments(&input.generics);
-    let arg_items = if let PathArguments::AngleBracketed(abga) = &arg {
-        abga.args
+    let arg_items = match &arg {
+        PathArguments::AngleBracketed(abga) => abga
+            .args
             .iter()
             .cloned()
             .map(generic_arg_to_type)
-            .collect::<Vec<_>>()
-    } else {
-        panic!()
+            .collect::<Vec<_>>(),
+        _ => Vec::new(),
     };
     quote! {
         #[automatically_derived]
@@ -209,6 +117,18 @@ fn emit_impl(input: &ItemEnum, flat_enum: &Path, leak_dict: &HashMap<Type, usize
     }
 }
 
+/// Returns the index of `ty` in `Leak<N, ..>`, which is given by `generate_leak_dict()`.
+fn leak_index(leak_dict: &HashMap<Type, usize>, ty: &Type) -> usize {
+    match leak_dict.get(ty) {
+        Some(n) => *n,
+        None => abort!(
+            ty.span(),
+            "cannot find the field type `{}` in the enum",
+            tokens_to_string(quote!(#ty))
+        ),
+    }
+}
+
 fn generate_leak_dict(input: &ItemEnum) -> HashMap<Type, usize> {
     let mut ret = HashMap::new();
     let mut num = 0;
@@ -223,22 +143,119 @@ fn generate_leak_dict(input: &ItemEnum) -> HashMap<Type, usize> {
     ret
 }
 
-pub fn flat_target(input: ItemEnum) -> TokenStream {
-    let mut flat_enum: Path = parse_quote!(::flat_enum);
-    for attr in &input.attrs {
-        if attr.path.is_ident("flat_enum") {
-            flat_enum = match attr.parse_args() {
-                Ok(v) => v,
-                Err(_) => abort!(
-                    &attr.bracket_token.span,
-                    "Only path item is acceptable in #[flat_enum(_)]"
-                ),
-            };
+/// Options of `#[derive(FlatTarget)]`, specified with `#[flat_enum(..)]`.
+#[derive(Default)]
+pub struct DeriveOptions {
+    /// The path of `flat_enum` crate.
+    pub krate: Option<Path>,
+    /// Emit the visitor trait.
+    pub visitor: bool,
+    /// Implement `codec::Encode` and `codec::Decode`.
+    pub codec: bool,
+    /// The type parameter mapped by `map()` and `to_ref()`.
+    pub map: Option<Ident>,
+    /// Emit the textually scoped macro instead of the exported one.
+    pub local: bool,
+}
+
+impl Parse for DeriveOptions {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut ret = Self::default();
+        while !input.is_empty() {
+            if input.peek(Ident) && input.peek2(token::Paren) {
+                let ident: Ident = input.parse()?;
+                if ident != "map" {
+                    return Err(Error::new(ident.span(), "Unknown option for #[flat_enum]"));
+                }
+                let content;
+                parenthesized!(content in input);
+                ret.map = Some(content.parse()?);
+            } else if input.peek(Token![crate]) && input.peek2(Token![=]) {
+                input.parse::<Token![crate]>()?;
+                input.parse::<Token![=]>()?;
+                ret.krate = Some(input.parse()?);
+            } else {
+                let ident: Ident = input.parse()?;
+                match ident.to_string().as_str() {
+                    "visitor" => ret.visitor = true,
+                    "codec" => ret.codec = true,
+                    "local" => ret.local = true,
+                    _ => return Err(Error::new(ident.span(), "Unknown option for #[flat_enum]")),
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
         }
+        Ok(ret)
+    }
+}
+
+impl DeriveOptions {
+    /// Parses `#[flat_enum(..)]` in `attrs`.
+    pub fn from_attrs(attrs: &[Attribute]) -> Self {
+        let mut ret = Self::default();
+        for attr in attrs {
+            if attr.path.is_ident("flat_enum") {
+                let options: Self = match attr.parse_args() {
+                    Ok(v) => v,
+                    Err(e) => abort!(
+                        e.span(),
+                        "{}", e;
+                        help = "Only `crate = path`, `visitor`, `codec`, `local` or `map(..)` is acceptable in #[flat_enum(_)]"
+                    ),
+                };
+                ret.krate = options.krate.or(ret.krate);
+                ret.visitor |= options.visitor;
+                ret.codec |= options.codec;
+                ret.local |= options.local;
+                ret.map = options.map.or(ret.map);
+            }
+        }
+        ret
+    }
+}
+
+/// Implements `FlatTarget` and emits the macro. `origins` are the origins of the variants,
+/// which is specified when the enum is a flat enum.
+pub fn emit_flat_target(
+    input: &ItemEnum,
+    flat_enum: &Path,
+    origins: &[Origin],
+    local: bool,
+) -> TokenStream {
+    let leak_dict = generate_leak_dict(input);
+    let rules = |header: &TokenStream| emit_macro(input, header, flat_enum, &leak_dict, origins);
+    quote! {
+        #{emit_impl(input, flat_enum, &leak_dict)}
+        #{emit_helper_macro(&input.ident, &input.vis, input, rules, local)}
     }
-    let leak_dict = generate_leak_dict(&input);
+}
+
+pub fn flat_target(input: ItemEnum) -> TokenStream {
+    let options = DeriveOptions::from_attrs(&input.attrs);
+    let flat_enum = options.krate.unwrap_or_else(|| parse_quote!(::flat_enum));
+    let mut origins: Vec<_> = input.variants.iter().map(Origin::find).collect();
+    origin::check_ids(&input, &origins);
+    origin::set_levels(&input, &mut origins);
     quote! {
-        #{emit_impl(&input, &flat_enum, &leak_dict)}
-        #{emit_macro_export_in_macro_namespace(&input, &flat_enum, &leak_dict)}
+        #{emit_flat_target(&input, &flat_enum, &origins, options.local)}
+        #(if options.visitor) {
+            #{visitor::emit(&input, &origins)}
+        }
+        #(if let Some(param) = &options.map) {
+            #{map::emit(&input, &flat_enum, param, &map::field_shapes(&input, &origins))}
+        }
+        #(if options.codec) {
+            #{codec::emit(&input, &flat_enum)}
+        }
+        #{variant_info::emit(&input, &flat_enum, None)}
+        #{descriptor::emit(
+            &input,
+            &flat_enum,
+            "FlatTarget",
+            &vec![None; input.variants.len()],
+            &origins.iter().map(|origin| origin.id.clone()).collect::<Vec<_>>(),
+        )}
     }
 }
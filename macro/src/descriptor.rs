@@ -0,0 +1,86 @@
+use crate::util::tokens_to_string;
+use proc_macro2::TokenStream;
+use syn::*;
+use template_quote::quote;
+
+fn emit_variant(
+    krate: &Path,
+    variant: &Variant,
+    flatten: Option<&Type>,
+    id: Option<&LitInt>,
+) -> TokenStream {
+    let kind = match &variant.fields {
+        Fields::Unit => quote!(Unit),
+        Fields::Unnamed(_) => quote!(Unnamed),
+        Fields::Named(_) => quote!(Named),
+    };
+    quote! {
+        #krate::VariantDescriptor {
+            name: #{variant.ident.to_string()},
+            kind: #krate::FieldsKind::#kind,
+            fields: &[
+                #(for field in &variant.fields) {
+                    #krate::FieldDescriptor {
+                        name: #(if let Some(ident) = &field.ident) {
+                            Some(#{ident.to_string()})
+                        }
+                        #(else) {
+                            None
+                        },
+                        ty: #{tokens_to_string(quote!(#{&field.ty}))},
+                    },
+                }
+            ],
+            flatten: #(if let Some(ty) = flatten) {
+                Some(#krate::__private::describe_flatten::<#ty>())
+            }
+            #(else) {
+                None
+            },
+            id: #(if let Some(id) = id) { Some(#id) } #(else) { None },
+        }
+    }
+}
+
+/// Implements `Describe`. `flattens` are the field types of `#[flatten]` variants, or `None`
+/// on the other variants. `ids` are the stable IDs, or `id_base` on `#[flatten]` variants.
+pub fn emit(
+    input: &ItemEnum,
+    krate: &Path,
+    kind: &str,
+    flattens: &[Option<Type>],
+    ids: &[Option<LitInt>],
+) -> TokenStream {
+    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
+    quote! {
+        #[automatically_derived]
+        impl #g_impl #krate::Describe for #{&input.ident} #g_type #g_where {
+            const DESCRIPTOR: &'static #krate::EnumDescriptor = &#krate::EnumDescriptor {
+                name: #{input.ident.to_string()},
+                path: concat!(module_path!(), "::", #{input.ident.to_string()}),
+                kind: #krate::EnumKind::#{Ident::new(kind, input.ident.span())},
+                variants: &[
+                    #(for ((variant, flatten), id) in input.variants.iter().zip(flattens).zip(ids)) {
+                        #{emit_variant(krate, variant, flatten.as_ref(), id.as_ref())},
+                    }
+                ],
+            };
+        }
+    }
+}
+
+/// Implements `Describe` on the flat enum, which shares the variants with the structured enum.
+pub fn emit_flat(input: &ItemEnum, krate: &Path, structured: &Path) -> TokenStream {
+    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
+    quote! {
+        #[automatically_derived]
+        impl #g_impl #krate::Describe for #{&input.ident} #g_type #g_where {
+            const DESCRIPTOR: &'static #krate::EnumDescriptor = &#krate::EnumDescriptor {
+                name: #{input.ident.to_string()},
+                path: concat!(module_path!(), "::", #{input.ident.to_string()}),
+                kind: #krate::EnumKind::Flat,
+                variants: <#structured as #krate::Describe>::DESCRIPTOR.variants,
+            };
+        }
+    }
+}
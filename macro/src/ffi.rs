@@ -0,0 +1,208 @@
+use crate::util::{emit_pattern, field_ids};
+use proc_macro2::{Span, TokenStream};
+use proc_macro_error::abort;
+use syn::spanned::Spanned;
+use syn::*;
+use template_quote::quote;
+
+/// Names of the fields in the payload struct, which are `_0`, `_1`, .. on tuple variants.
+fn field_names(variant: &Variant) -> Vec<Ident> {
+    variant
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            field
+                .ident
+                .clone()
+                .unwrap_or_else(|| Ident::new(&format!("_{}", i), Span::call_site()))
+        })
+        .collect()
+}
+
+/// Emits the `#[repr(C)]` struct `{Enum}Ffi` which has the tag and the payload union, the tag
+/// enum `{Enum}FfiTag`, and the conversions on the flat enum.
+pub fn emit(input: &ItemEnum, krate: &Path) -> TokenStream {
+    if input.variants.is_empty() {
+        abort!(
+            input.ident.span(),
+            "The flat enum without variants cannot be exported to C"
+        );
+    }
+    if !input.generics.params.is_empty() {
+        abort!(
+            input.generics.span(),
+            "The generic flat enum cannot be exported to C";
+            help = "Define the flat enum of the concrete types, like `#[flat(Enum2<u8>, ffi)]`"
+        );
+    }
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let name = |suffix: &str| Ident::new(&format!("{}{}", ident, suffix), ident.span());
+    let ffi = name("Ffi");
+    let tag = name("FfiTag");
+    let payload = name("FfiPayload");
+    let variant_struct = |variant: &Variant| name(&format!("Ffi_{}", variant.ident));
+    let with_fields: Vec<&Variant> = input
+        .variants
+        .iter()
+        .filter(|variant| !variant.fields.is_empty())
+        .collect();
+    let header_name = ffi.to_string();
+    quote! {
+        #[doc = #{format!("Tags of [`{}`], which is the index of the variant.", ffi)}]
+        #[repr(u32)]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        #vis enum #tag {
+            #(for (i, variant) in input.variants.iter().enumerate()) {
+                #[doc = #{format!("Tag of `{}::{}`.", ident, &variant.ident)}]
+                #{&variant.ident} = #{i as u32},
+            }
+        }
+
+        #(for variant in &with_fields) {
+            #[doc = #{format!("Fields of `{}::{}` in [`{}`].", ident, &variant.ident, payload)}]
+            #[repr(C)]
+            #[derive(Clone, Copy)]
+            #[allow(non_camel_case_types)]
+            #vis struct #{variant_struct(variant)} {
+                #(for (field, name) in variant.fields.iter().zip(field_names(variant))) {
+                    #[doc = #{format!("Field `{}` of `{}::{}`.", name, ident, &variant.ident)}]
+                    pub #name: #{&field.ty},
+                }
+            }
+        }
+
+        #[doc = #{format!("Payload of [`{}`], which has the fields of the variant given by the tag.", ffi)}]
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        #[allow(non_snake_case)]
+        #vis union #payload {
+            _reserved: u8,
+            #(for variant in &with_fields) {
+                #{&variant.ident}: #{variant_struct(variant)},
+            }
+        }
+
+        #[doc = #{format!("C representation of [`{}`], converted with `to_ffi()` and `from_ffi()`.", ident)}]
+        ///
+        /// The C declaration is generated with `c_header()`. The payload of the variant given by
+        /// the tag should be initialized when the value is passed from C.
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        #vis struct #ffi {
+            tag: u32,
+            payload: #payload,
+        }
+
+        #[allow(dead_code)]
+        const _: () = {
+            fn assert_ffi_safe<T: #krate::ffi::FfiSafe>() {}
+            fn assert_fields() {
+                #(for field in input.variants.iter().flat_map(|variant| &variant.fields)) {
+                    assert_ffi_safe::<#{&field.ty}>();
+                }
+            }
+        };
+
+        #[automatically_derived]
+        impl #ffi {
+            /// Returns the tag, or `None` if it is not a tag of any variant.
+            #vis fn tag(&self) -> Option<#tag> {
+                match self.tag {
+                    #(for (i, variant) in input.variants.iter().enumerate()) {
+                        #{i as u32} => Some(#tag::#{&variant.ident}),
+                    }
+                    _ => None,
+                }
+            }
+
+            /// Returns the C declarations of the tag, the payload and this struct.
+            #vis fn c_header() -> String {
+                let mut out = String::new();
+                out.push_str(#{format!("#ifndef {0}_H\n#define {0}_H\n\n#include <stdint.h>\n\n", header_name.to_uppercase())});
+                out.push_str(#{format!("typedef enum {} {{\n", tag)});
+                #(for (i, variant) in input.variants.iter().enumerate()) {
+                    out.push_str(#{format!("    {}_{} = {},\n", tag, &variant.ident, i)});
+                }
+                out.push_str(#{format!("}} {};\n\n", tag)});
+                #(for variant in &with_fields) {
+                    out.push_str(#{format!("typedef struct {} {{\n", variant_struct(variant))});
+                    #(for (field, name) in variant.fields.iter().zip(field_names(variant))) {
+                        out.push_str("    ");
+                        out.push_str(&<#{&field.ty} as #krate::ffi::FfiSafe>::c_decl(#{name.to_string()}));
+                        out.push_str(";\n");
+                    }
+                    out.push_str(#{format!("}} {};\n\n", variant_struct(variant))});
+                }
+                out.push_str(#{format!("typedef union {} {{\n    uint8_t _reserved;\n", payload)});
+                #(for variant in &with_fields) {
+                    out.push_str(#{format!("    {} {};\n", variant_struct(variant), &variant.ident)});
+                }
+                out.push_str(#{format!("}} {};\n\n", payload)});
+                out.push_str(#{format!(
+                    "/* `tag` is one of `{}`. */\ntypedef struct {} {{\n    uint32_t tag;\n    {} payload;\n}} {};\n\n#endif\n",
+                    tag, ffi, payload, ffi
+                )});
+                out
+            }
+        }
+
+        #[automatically_derived]
+        impl #ident {
+            #[doc = #{format!("Converts the value into [`{}`].", ffi)}]
+            #vis fn to_ffi(&self) -> #ffi {
+                // SAFETY: zero is valid for the tag and `FfiSafe` fields, and the payload is
+                // always fully initialized.
+                let mut ffi: #ffi = unsafe { ::core::mem::zeroed() };
+                match self {
+                    #(for (i, variant) in input.variants.iter().enumerate()) {
+                        #(let ids = field_ids(variant, "a")) {
+                            #{emit_pattern(&quote!(Self), variant, &ids)} => {
+                                ffi.tag = #{i as u32};
+                                #(if !variant.fields.is_empty()) {
+                                    ffi.payload.#{&variant.ident} = #{variant_struct(variant)} {
+                                        #(for (name, id) in field_names(variant).iter().zip(&ids)) {
+                                            #name: *#id,
+                                        }
+                                    };
+                                }
+                            }
+                        }
+                    }
+                }
+                ffi
+            }
+
+            #[doc = #{format!("Converts [`{}`] into the value. Returns `None` if the tag is unknown.", ffi)}]
+            ///
+            /// # Safety
+            ///
+            /// The payload of the variant given by the tag should be initialized, as in the
+            /// value returned by `to_ffi()`. The other bytes of the payload may be uninitialized.
+            #vis unsafe fn from_ffi(ffi: &#ffi) -> Option<Self> {
+                match ffi.tag {
+                    #(for (i, variant) in input.variants.iter().enumerate()) {
+                        #{i as u32} => {
+                            #(if variant.fields.is_empty()) {
+                                Some(#{emit_pattern::<Ident>(&quote!(Self), variant, &[])})
+                            }
+                            #(else) {
+                                // SAFETY: the payload of the tag is initialized as required by
+                                // `from_ffi()`, and every bit pattern is valid for `FfiSafe`
+                                // fields.
+                                let fields = unsafe { ffi.payload.#{&variant.ident} };
+                                Some(#{emit_pattern(
+                                    &quote!(Self),
+                                    variant,
+                                    &field_names(variant).iter().map(|name| quote!(fields.#name)).collect::<Vec<_>>(),
+                                )})
+                            }
+                        }
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+}
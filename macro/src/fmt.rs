@@ -0,0 +1,66 @@
+use crate::util::{add_bounds, emit_pattern, field_ids, field_types};
+use proc_macro2::TokenStream;
+use syn::*;
+use template_quote::quote;
+
+/// Formats the variant with the given name.
+fn emit_debug_variant(variant: &Variant) -> TokenStream {
+    let ids = field_ids(variant, "a");
+    let pat = emit_pattern(&quote!(Self), variant, &ids);
+    match &variant.fields {
+        Fields::Named(fields) => quote! {
+            #pat => {
+                f.debug_struct(name)
+                    #(for (field, id) in fields.named.iter().zip(&ids)) {
+                        .field(#{field.ident.as_ref().unwrap().to_string()}, #id)
+                    }
+                    .finish()
+            }
+        },
+        Fields::Unnamed(_) => quote! {
+            #pat => {
+                f.debug_tuple(name)
+                    #(for id in &ids) { .field(#id) }
+                    .finish()
+            }
+        },
+        Fields::Unit => quote! {
+            #pat => f.write_str(name),
+        },
+    }
+}
+
+/// Implements `Debug` on the flat enum, which prints the value as the structured enum.
+pub fn emit_debug_structured(input: &ItemEnum, krate: &Path, structured: &Path) -> TokenStream {
+    let generics = add_bounds(
+        &input.generics,
+        field_types(input),
+        &parse_quote!(::core::fmt::Debug),
+    );
+    let (g_impl, g_type, g_where) = generics.split_for_impl();
+    quote! {
+        #[automatically_derived]
+        impl #g_impl ::core::fmt::Debug for #{&input.ident} #g_type #g_where {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                #(if input.variants.is_empty()) {
+                    match *self {}
+                }
+                #(else) {
+                    let fields = |f: &mut ::core::fmt::Formatter<'_>, name| match self {
+                        #(for variant in &input.variants) {
+                            #{emit_debug_variant(variant)}
+                        }
+                    };
+                    ::core::fmt::Debug::fmt(
+                        &#krate::__private::DebugStructured::new(
+                            <#structured as #krate::Describe>::DESCRIPTOR,
+                            #krate::VariantInfo::origin_path(self),
+                            &fields,
+                        ),
+                        f,
+                    )
+                }
+            }
+        }
+    }
+}
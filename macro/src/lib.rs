@@ -1,11 +1,23 @@
 mod attr_flat;
 mod attr_into_flat;
+mod cmp;
+mod codec;
+mod common;
+mod delegate;
 mod derive_macro;
+mod descriptor;
+mod ffi;
+mod fmt;
+mod map;
+mod origin;
+mod serde;
 mod util;
+mod variant_info;
+mod visitor;
 
 use proc_macro::TokenStream;
 use proc_macro_error::proc_macro_error;
-use syn::{parse_macro_input, ItemEnum};
+use syn::{parse_macro_input, ItemEnum, ItemTrait};
 
 #[proc_macro_error]
 #[proc_macro_derive(FlatTarget, attributes(flat_enum))]
@@ -34,3 +46,39 @@ pub fn flat(attr: TokenStream, input: TokenStream) -> TokenStream {
     ret
     .into()
 }
+
+#[doc(hidden)]
+#[proc_macro_error]
+#[proc_macro_attribute]
+pub fn flat_impl(attr: TokenStream, input: TokenStream) -> TokenStream {
+    attr_flat::flat_impl(
+        parse_macro_input!(attr),
+        parse_macro_input!(input as ItemEnum),
+    )
+    .into()
+}
+
+#[doc(hidden)]
+#[proc_macro_error]
+#[proc_macro]
+pub fn structured_impl(input: TokenStream) -> TokenStream {
+    attr_into_flat::structured_impl(parse_macro_input!(input)).into()
+}
+
+#[proc_macro_error]
+#[proc_macro_attribute]
+pub fn delegatable(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let krate = if attr.is_empty() {
+        None
+    } else {
+        Some(parse_macro_input!(attr as delegate::CratePath).0)
+    };
+    delegate::delegatable(krate, parse_macro_input!(input as ItemTrait)).into()
+}
+
+#[doc(hidden)]
+#[proc_macro_error]
+#[proc_macro]
+pub fn delegate_impl(input: TokenStream) -> TokenStream {
+    delegate::delegate_impl(parse_macro_input!(input)).into()
+}
@@ -0,0 +1,275 @@
+use crate::origin::{Origin, Param, Substitute};
+use crate::util::{emit_pattern, field_ids, tokens_to_string, ungroup};
+use proc_macro2::{Span, TokenStream, TokenTree};
+use proc_macro_error::abort;
+use syn::spanned::Spanned;
+use syn::visit_mut::VisitMut;
+use syn::*;
+use template_quote::{quote, quote_spanned};
+
+fn mentions(tokens: TokenStream, param: &Ident) -> bool {
+    tokens.into_iter().any(|tt| match tt {
+        TokenTree::Ident(ident) => &ident == param,
+        TokenTree::Group(g) => mentions(g.stream(), param),
+        _ => false,
+    })
+}
+
+fn is_param(ty: &Type, param: &Ident) -> bool {
+    matches!(ungroup(ty), Type::Path(TypePath { qself: None, path }) if path.is_ident(param))
+}
+
+/// Returns the type arguments of the last segment.
+fn type_args(path: &Path) -> Vec<&Type> {
+    match path.segments.last().map(|seg| &seg.arguments) {
+        Some(PathArguments::AngleBracketed(args)) => args
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Emits the expression which maps the field `x` of type `ty`. With `to_ref`, `x` is the
+/// reference to the field. Returns `None` if the field does not depend on `param`.
+fn map_field(ty: &Type, param: &Ident, x: &Ident, to_ref: bool) -> Option<TokenStream> {
+    let ty = ungroup(ty);
+    if !mentions(quote!(#ty), param) {
+        return None;
+    }
+    let unsupported = || -> ! {
+        abort!(
+            ty.span(),
+            "Cannot map the field of type `{}`",
+            tokens_to_string(quote!(#ty));
+            help = "Expected `{0}`, `Vec<{0}>`, `Option<{0}>`, `[{0}; N]`, or the enum with `map({0})`", param
+        )
+    };
+    Some(match ty {
+        _ if is_param(ty, param) => {
+            if to_ref {
+                quote!(#x)
+            } else {
+                quote!(f(#x))
+            }
+        }
+        Type::Array(TypeArray { elem, len, .. }) if is_param(elem, param) => {
+            if mentions(quote!(#len), param) {
+                unsupported()
+            }
+            if to_ref {
+                quote!(#x.each_ref())
+            } else {
+                quote!(#x.map(&mut f))
+            }
+        }
+        Type::Path(TypePath { qself: None, path }) => {
+            let args = type_args(path);
+            let params = args.iter().filter(|arg| is_param(arg, param)).count();
+            if params != 1
+                || args
+                    .iter()
+                    .any(|arg| !is_param(arg, param) && mentions(quote!(#arg), param))
+            {
+                unsupported()
+            }
+            match (
+                path.segments.last().unwrap().ident.to_string().as_str(),
+                to_ref,
+            ) {
+                ("Vec", false) => quote!(#x.into_iter().map(&mut f).collect()),
+                ("Vec", true) => quote!(#x.iter().collect()),
+                ("Option", false) => quote!(#x.map(&mut f)),
+                ("Option", true) => quote!(#x.as_ref()),
+                // The enums with `map(..)`, which are checked by `assert_map_enum()`
+                (_, false) => quote!(#x.map(&mut f)),
+                (_, true) => quote!(#x.to_ref()),
+            }
+        }
+        _ => unsupported(),
+    })
+}
+
+fn fresh_ident(generics: &Generics, name: &str) -> Ident {
+    let used = |ident: &Ident| {
+        generics.params.iter().any(|param| match param {
+            GenericParam::Type(tp) => &tp.ident == ident,
+            GenericParam::Const(cp) => &cp.ident == ident,
+            _ => false,
+        })
+    };
+    (0..)
+        .map(|i| match i {
+            0 => Ident::new(name, Span::call_site()),
+            i => Ident::new(&format!("{}{}", name, i), Span::call_site()),
+        })
+        .find(|ident| !used(ident))
+        .unwrap()
+}
+
+/// Emits `where` predicates required by the enum whose `param` is replaced with `ty`.
+fn substituted_predicates(generics: &Generics, param: &Ident, ty: &Type) -> Vec<WherePredicate> {
+    let mut subst = Substitute::new(&[(Param::Ident(param.clone()), ty.clone())]);
+    let mut ret = Vec::new();
+    for gp in &generics.params {
+        if let GenericParam::Type(tp) = gp {
+            if &tp.ident == param && !tp.bounds.is_empty() {
+                let bounds = &tp.bounds;
+                ret.push(parse_quote!(#ty: #bounds));
+            }
+        }
+    }
+    for pred in generics.where_clause.iter().flat_map(|wc| &wc.predicates) {
+        if mentions(quote!(#pred), param) {
+            let mut pred = pred.clone();
+            subst.visit_where_predicate_mut(&mut pred);
+            ret.push(pred);
+        }
+    }
+    ret
+}
+
+/// Returns the field types of each variant as written in the original enum.
+pub fn field_shapes(input: &ItemEnum, origins: &[Origin]) -> Vec<Vec<Type>> {
+    input
+        .variants
+        .iter()
+        .zip(origins)
+        .map(|(variant, origin)| origin.field_types_or(variant))
+        .collect()
+}
+
+/// Returns the field types which are mapped as the enums with `map(..)`.
+fn nested_enums<'a>(shapes: &'a [Vec<Type>], param: &Ident) -> Vec<&'a Type> {
+    shapes
+        .iter()
+        .flatten()
+        .map(ungroup)
+        .filter(|ty| match ty {
+            _ if is_param(ty, param) => false,
+            Type::Path(TypePath { qself: None, path }) => {
+                let name = path.segments.last().unwrap().ident.to_string();
+                name != "Vec" && name != "Option" && mentions(quote!(#ty), param)
+            }
+            _ => false,
+        })
+        .collect()
+}
+
+/// Emits `map()` and `to_ref()` which map the generic parameter `param` in the fields.
+/// `shapes` are the field types of each variant as written in the original enum, which decide
+/// how the fields are mapped.
+pub fn emit(input: &ItemEnum, krate: &Path, param: &Ident, shapes: &[Vec<Type>]) -> TokenStream {
+    if !input.generics.type_params().any(|tp| &tp.ident == param) {
+        abort!(
+            param.span(),
+            "`{}` is not a type parameter of the enum",
+            param
+        );
+    }
+    let ident = &input.ident;
+    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
+    let out = fresh_ident(&input.generics, "B");
+    let out_ty: Type = parse_quote!(#out);
+    let ref_ty: Type = parse_quote!(&'__flat_enum_ref #param);
+    let args_with = |ty: &Type| -> Vec<TokenStream> {
+        input
+            .generics
+            .params
+            .iter()
+            .map(|gp| match gp {
+                GenericParam::Lifetime(lt) => {
+                    let lt = &lt.lifetime;
+                    quote!(#lt)
+                }
+                GenericParam::Type(tp) if &tp.ident == param => quote!(#ty),
+                GenericParam::Type(tp) => {
+                    let ident = &tp.ident;
+                    quote!(#ident)
+                }
+                GenericParam::Const(cp) => {
+                    let ident = &cp.ident;
+                    quote!(#ident)
+                }
+            })
+            .collect()
+    };
+    let out_args = args_with(&out_ty);
+    let ref_args = args_with(&ref_ty);
+    let arms = |to_ref: bool| -> TokenStream {
+        quote! {
+            #(for (variant, shape) in input.variants.iter().zip(shapes)) {
+                #(let ids = field_ids(variant, "a")) {
+                    #(let exprs = shape.iter().zip(&ids).map(|(ty, id)| {
+                        map_field(ty, param, id, to_ref).unwrap_or_else(|| {
+                            if to_ref {
+                                quote!(::core::clone::Clone::clone(#id))
+                            } else {
+                                quote!(#id)
+                            }
+                        })
+                    }).collect::<Vec<_>>()) {
+                        #{emit_pattern(&quote!(Self), variant, &ids)}
+                            => #{emit_pattern(&quote!(#ident), variant, &exprs)},
+                    }
+                }
+            }
+        }
+    };
+    // Fields which do not depend on `param` are cloned in `to_ref()`.
+    let cloned: Vec<&Type> = input
+        .variants
+        .iter()
+        .zip(shapes)
+        .flat_map(|(variant, shape)| variant.fields.iter().zip(shape))
+        .filter(|(_, shape)| !mentions(quote!(#shape), param))
+        .map(|(field, _)| &field.ty)
+        .collect();
+    quote! {
+        #[automatically_derived]
+        impl #g_impl #krate::__private::MapEnum for #ident #g_type #g_where {}
+
+        #[automatically_derived]
+        #[allow(clippy::needless_lifetimes)]
+        impl #g_impl #ident #g_type #g_where {
+            #[doc = #{format!("Maps `{}` in the fields of the variant with `f`.", param)}]
+            #{&input.vis} fn map<#out>(self, mut f: impl FnMut(#param) -> #out) -> #ident<#(#out_args),*>
+            where
+                #(for pred in substituted_predicates(&input.generics, param, &out_ty)) { #pred, }
+            {
+                let _ = &mut f;
+                #(for ty in nested_enums(shapes, param)) {
+                    #{quote_spanned! {ty.span() => #krate::__private::assert_map_enum::<#ty>();}}
+                }
+                #(if input.variants.is_empty()) {
+                    match self {}
+                }
+                #(else) {
+                    match self {
+                        #{arms(false)}
+                    }
+                }
+            }
+
+            #[doc = #{format!("Converts `&{0}` into `{0}` which has the references to `{1}`. The fields which do not contain `{1}` are cloned.", ident, param)}]
+            #{&input.vis} fn to_ref<'__flat_enum_ref>(&'__flat_enum_ref self) -> #ident<#(#ref_args),*>
+            where
+                #(for pred in substituted_predicates(&input.generics, param, &ref_ty)) { #pred, }
+                #(for ty in &cloned) { #ty: ::core::clone::Clone, }
+            {
+                #(if input.variants.is_empty()) {
+                    match *self {}
+                }
+                #(else) {
+                    match self {
+                        #{arms(true)}
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,454 @@
+use crate::util::ungroup;
+use proc_macro2::{Literal, TokenStream};
+use proc_macro_error::abort;
+use std::collections::HashMap;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::visit_mut::VisitMut;
+use syn::*;
+use template_quote::{quote, ToTokens};
+
+mod kw {
+    syn::custom_keyword!(origin);
+    syn::custom_keyword!(ty);
+    syn::custom_keyword!(params);
+    syn::custom_keyword!(fields);
+    syn::custom_keyword!(id);
+    syn::custom_keyword!(levels);
+    syn::custom_keyword!(discriminant);
+}
+
+/// Generic parameter of the flattened enum, which appears in `params(..)`.
+#[derive(Clone)]
+pub enum Param {
+    Lifetime(Lifetime),
+    Ident(Ident),
+}
+
+impl Parse for Param {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(Lifetime) {
+            Ok(Self::Lifetime(input.parse()?))
+        } else {
+            Ok(Self::Ident(input.parse()?))
+        }
+    }
+}
+
+/// An enum which a variant of the flat enum goes through, written as `Enum1(0, 3)` in
+/// `levels(..)`, or `Enum1(0, 3, discriminant)` if the enum has custom discriminants.
+#[derive(Clone)]
+pub struct Level {
+    /// The name of the enum. It is the structured enum for the flat enums.
+    pub name: Ident,
+    /// The index of the variant or the `#[flatten]` variant in the enum.
+    pub index: usize,
+    /// The number of the variants of the enum.
+    pub count: usize,
+    /// The enum has explicit discriminants or `#[repr(..)]`, so the derived impls do not follow
+    /// `index`.
+    pub discriminant: bool,
+}
+
+impl Parse for Level {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let name = input.parse()?;
+        let content;
+        parenthesized!(content in input);
+        let index: LitInt = content.parse()?;
+        content.parse::<Token![,]>()?;
+        let count: LitInt = content.parse()?;
+        let discriminant = content.parse::<Option<Token![,]>>()?.is_some()
+            && content.parse::<Option<kw::discriminant>>()?.is_some();
+        Ok(Self {
+            name,
+            index: index.base10_parse()?,
+            count: count.base10_parse()?,
+            discriminant,
+        })
+    }
+}
+
+/// A variant of the structured enum, passed to `#[flat_impl]` like `#[flatten] Enum1`.
+pub struct StructuredVariant {
+    pub ident: Ident,
+    pub flatten: bool,
+}
+
+impl Parse for StructuredVariant {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        Ok(Self {
+            ident: input.parse()?,
+            flatten: attrs.iter().any(|attr| attr.path.is_ident("flatten")),
+        })
+    }
+}
+
+impl ToTokens for StructuredVariant {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        if self.flatten {
+            tokens.extend(quote!(#[flatten]));
+        }
+        self.ident.to_tokens(tokens);
+    }
+}
+
+/// Where a variant of the flat enum comes from. It is attached to the variant as
+/// `#[flat_enum(origin(..))]` while the flat enum is expanded, and removed in `#[flat_impl]`.
+/// The stable ID written by users as `#[flat_enum(id = N)]` is also parsed into it.
+#[derive(Clone, Default)]
+pub struct Origin {
+    /// Idents of `#[flatten]` variants, from the outermost one.
+    pub groups: Vec<Ident>,
+    /// The field type of the outermost `#[flatten]` variant.
+    pub ty: Option<Type>,
+    /// Generic parameters of the enum which the variant is defined in, and the arguments given
+    /// in the outermost `#[flatten]` variant.
+    pub params: Vec<(Param, Type)>,
+    /// Field types as written in the enum which the variant is defined in.
+    pub fields: Option<Vec<Type>>,
+    /// The stable ID, which is the sum of the ID of the variant and `id_base` of the
+    /// `#[flatten]` variants.
+    pub id: Option<LitInt>,
+    /// The enums which the variant goes through, from the outermost one, which are used to
+    /// emulate the derived traits and the serialization of the structured enum.
+    pub levels: Vec<Level>,
+}
+
+fn parse_paren_list<T: Parse>(input: ParseStream) -> Result<Vec<T>> {
+    let content;
+    parenthesized!(content in input);
+    Ok(Punctuated::<T, Token![,]>::parse_terminated(&content)?
+        .into_iter()
+        .collect())
+}
+
+impl Parse for Origin {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut ret = Self::default();
+        while !input.is_empty() {
+            let lookahead = input.lookahead1();
+            if lookahead.peek(kw::origin) {
+                input.parse::<kw::origin>()?;
+                ret.groups = parse_paren_list(input)?;
+            } else if lookahead.peek(kw::id) {
+                let id = input.parse::<kw::id>()?;
+                // `id = N` written by users, or `id(base, .., N)` passed through the macros
+                let ids: Vec<LitInt> = if input.parse::<Option<Token![=]>>()?.is_some() {
+                    vec![input.parse()?]
+                } else {
+                    parse_paren_list(input)?
+                };
+                let mut sum = 0u32;
+                for lit in &ids {
+                    sum = sum
+                        .checked_add(lit.base10_parse()?)
+                        .ok_or_else(|| Error::new(lit.span(), "The stable ID overflows u32"))?;
+                }
+                ret.id = Some(LitInt::new(
+                    &sum.to_string(),
+                    ids.last().map_or(id.span, |lit| lit.span()),
+                ));
+            } else if lookahead.peek(kw::ty) {
+                input.parse::<kw::ty>()?;
+                input.parse::<Token![=]>()?;
+                ret.ty = Some(input.parse()?);
+            } else if lookahead.peek(kw::params) {
+                input.parse::<kw::params>()?;
+                let params: Vec<Param> = parse_paren_list(input)?;
+                input.parse::<Token![=]>()?;
+                let args: Vec<Type> = parse_paren_list(input)?;
+                ret.params = params.into_iter().zip(args).collect();
+            } else if lookahead.peek(kw::levels) {
+                input.parse::<kw::levels>()?;
+                ret.levels = parse_paren_list(input)?;
+            } else if lookahead.peek(kw::fields) {
+                input.parse::<kw::fields>()?;
+                ret.fields = Some(parse_paren_list(input)?);
+            } else {
+                return Err(lookahead.error());
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(ret)
+    }
+}
+
+/// Replaces the generic parameters with the arguments.
+pub struct Substitute {
+    types: HashMap<Ident, Type>,
+    lifetimes: HashMap<Lifetime, Lifetime>,
+    consts: HashMap<Ident, Expr>,
+}
+
+impl Substitute {
+    pub fn new(params: &[(Param, Type)]) -> Self {
+        let mut ret = Self {
+            types: HashMap::new(),
+            lifetimes: HashMap::new(),
+            consts: HashMap::new(),
+        };
+        for (param, arg) in params {
+            // Lifetimes and consts are passed as `&'a ()` and `[(); N]`, like `Leak`.
+            match (param, ungroup(arg)) {
+                (
+                    Param::Lifetime(lt),
+                    Type::Reference(TypeReference {
+                        lifetime: Some(arg),
+                        ..
+                    }),
+                ) => {
+                    ret.lifetimes.insert(lt.clone(), arg.clone());
+                }
+                (Param::Ident(ident), Type::Array(TypeArray { len, .. })) => {
+                    ret.consts.insert(ident.clone(), len.clone());
+                }
+                (Param::Ident(ident), arg) => {
+                    ret.types.insert(ident.clone(), arg.clone());
+                }
+                _ => (),
+            }
+        }
+        ret
+    }
+}
+
+impl VisitMut for Substitute {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(TypePath { qself: None, path }) = ty {
+            if let Some(ident) = path.get_ident() {
+                if let Some(arg) = self.types.get(ident) {
+                    *ty = ungroup(arg).clone();
+                    return;
+                }
+            }
+        }
+        visit_mut::visit_type_mut(self, ty)
+    }
+
+    fn visit_lifetime_mut(&mut self, lt: &mut Lifetime) {
+        if let Some(arg) = self.lifetimes.get(lt) {
+            *lt = arg.clone();
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Path(ExprPath {
+            qself: None, path, ..
+        }) = expr
+        {
+            if let Some(arg) = path.get_ident().and_then(|ident| self.consts.get(ident)) {
+                *expr = arg.clone();
+                return;
+            }
+        }
+        visit_mut::visit_expr_mut(self, expr)
+    }
+}
+
+impl Origin {
+    fn is_origin_attr(attr: &Attribute) -> bool {
+        attr.path.is_ident("flat_enum") && attr.parse_args::<Origin>().is_ok()
+    }
+
+    /// Returns the content of `#[flat_enum(origin(..))]` or `#[flat_enum(id = N)]` on the
+    /// variant.
+    pub fn find(variant: &Variant) -> Self {
+        variant
+            .attrs
+            .iter()
+            .find_map(|attr| {
+                if attr.path.is_ident("flat_enum") {
+                    match attr.parse_args::<Origin>() {
+                        Ok(origin) => Some(origin),
+                        Err(e) => abort!(e.span(), "{}", e),
+                    }
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    /// Removes `#[flat_enum(origin(..))]` from the variant and returns its content.
+    pub fn take(variant: &mut Variant) -> Self {
+        let ret = Self::find(variant);
+        variant.attrs.retain(|attr| !Self::is_origin_attr(attr));
+        ret
+    }
+
+    /// Emits the attribute for `variant` of the enum flattened as `group`, which has the field
+    /// type `ty`. `params` are the generic parameters of the enum, and `args` are the arguments.
+    /// `id_base` is `id_base` of the `#[flatten]` variant followed by a comma, or empty.
+    pub fn to_attr(
+        &self,
+        variant: &Variant,
+        group: &TokenStream,
+        ty: &TokenStream,
+        params: &[Param],
+        args: &TokenStream,
+        id_base: &TokenStream,
+    ) -> TokenStream {
+        quote! {
+            #[flat_enum(
+                origin(#group #(for g in &self.groups) { , #g }),
+                ty = #ty,
+                params(
+                    #(for param in params), {
+                        #(if let Param::Lifetime(lt) = param) { #lt }
+                        #(if let Param::Ident(ident) = param) { #ident }
+                    }
+                ) = #args,
+                fields(#(for ty in &self.field_types_or(variant)), { #ty }),
+                levels(
+                    #(for level in &self.levels), {
+                        #{&level.name}(
+                            #{Literal::usize_unsuffixed(level.index)},
+                            #{Literal::usize_unsuffixed(level.count)}
+                            #(if level.discriminant) { , discriminant }
+                        )
+                    }
+                ),
+                #(if let Some(id) = &self.id) { id(#id_base #id), }
+            )]
+        }
+    }
+
+    /// Variant names from the outermost `#[flatten]` variant to `ident`.
+    pub fn path(&self, ident: &Ident) -> Vec<String> {
+        self.groups
+            .iter()
+            .chain(Some(ident))
+            .map(|ident| ident.to_string())
+            .collect()
+    }
+
+    /// Field types of the variant, in terms of the generic parameters of the enum which has the
+    /// variant. Unlike the field types in the flat enum, they keep the shape as written in the
+    /// original enum, like `Vec<A>`. Returns the field types of `variant` if it is not flattened.
+    pub fn field_types_or(&self, variant: &Variant) -> Vec<Type> {
+        match &self.fields {
+            Some(fields) => {
+                let mut subst = Substitute::new(&self.params);
+                fields
+                    .iter()
+                    .map(|ty| {
+                        let mut ty = ty.clone();
+                        subst.visit_type_mut(&mut ty);
+                        ty
+                    })
+                    .collect()
+            }
+            None => variant
+                .fields
+                .iter()
+                .map(|field| field.ty.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Sets the levels of the variants of `input`, which is not a flat enum.
+pub fn set_levels(input: &ItemEnum, origins: &mut [Origin]) {
+    let count = input.variants.len();
+    let discriminant = has_custom_discriminants(input);
+    for (index, origin) in origins.iter_mut().enumerate() {
+        origin.levels = vec![Level {
+            name: input.ident.clone(),
+            index,
+            count,
+            discriminant,
+        }];
+    }
+}
+
+/// Returns whether `input` has explicit discriminants or `#[repr(..)]`.
+pub fn has_custom_discriminants(input: &ItemEnum) -> bool {
+    input
+        .variants
+        .iter()
+        .any(|variant| variant.discriminant.is_some())
+        || input.attrs.iter().any(|attr| attr.path.is_ident("repr"))
+}
+
+/// Prepends the level of the structured enum `structured`, which has `variants`, to the levels of
+/// the variants of the flat enum `input`. `discriminant` tells whether the structured enum has
+/// custom discriminants.
+pub fn add_structured_level(
+    input: &ItemEnum,
+    origins: &mut [Origin],
+    structured: &Path,
+    variants: &[StructuredVariant],
+    discriminant: bool,
+) {
+    let name = &structured.segments.last().unwrap().ident;
+    for (variant, origin) in input.variants.iter().zip(origins) {
+        let ident = origin.groups.first().unwrap_or(&variant.ident);
+        let index = match variants.iter().position(|v| &v.ident == ident) {
+            Some(index) => index,
+            None => abort!(
+                ident.span(),
+                "cannot find the variant `{}` in `{}`",
+                ident,
+                name
+            ),
+        };
+        origin.levels.insert(
+            0,
+            Level {
+                name: name.clone(),
+                index,
+                count: variants.len(),
+                discriminant,
+            },
+        );
+    }
+}
+
+/// Checks that the stable IDs are given to all or none of the variants, and are unique.
+pub fn check_ids(input: &ItemEnum, origins: &[Origin]) {
+    if origins.iter().all(|origin| origin.id.is_none()) {
+        return;
+    }
+    let missing: Vec<_> = input
+        .variants
+        .iter()
+        .zip(origins)
+        .filter(|(_, origin)| origin.id.is_none())
+        .map(|(variant, _)| variant.ident.to_string())
+        .collect();
+    if !missing.is_empty() {
+        abort!(
+            input.ident.span(),
+            "The stable ID is missing in the variants: {}",
+            missing.join(", ");
+            help = "Specify `#[flat_enum(id = N)]` on all variants, or on none of them"
+        );
+    }
+    let mut seen: Vec<(u32, &Ident)> = Vec::new();
+    for (variant, origin) in input.variants.iter().zip(origins) {
+        let id = origin.id.as_ref().unwrap();
+        let value: u32 = match id.base10_parse() {
+            Ok(value) => value,
+            Err(e) => abort!(
+                id.span(),
+                "{}", e;
+                help = "The stable ID should be in the range of `u32`"
+            ),
+        };
+        if let Some((_, other)) = seen.iter().find(|(v, _)| *v == value) {
+            abort!(
+                id.span(),
+                "The stable ID {} of `{}` is the same as `{}`",
+                value,
+                variant.ident,
+                other;
+                help = "Use `#[flatten(id_base = N)]` to separate the IDs of the flattened enums"
+            );
+        }
+        seen.push((value, &variant.ident));
+    }
+}
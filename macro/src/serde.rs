@@ -0,0 +1,338 @@
+use crate::origin::Origin;
+use crate::util::{add_bounds, emit_pattern, field_ids, field_types};
+use proc_macro2::{Literal, Span, TokenStream};
+use syn::parse::{Parse, ParseStream};
+use syn::*;
+use template_quote::quote;
+
+mod kw {
+    syn::custom_keyword!(repr);
+}
+
+/// The representation given in `serde(repr = "..")`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Repr {
+    /// Externally tagged with the variant names of the flat enum.
+    Flat,
+    /// The same as the structured enum.
+    Structured,
+}
+
+impl Parse for Repr {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<kw::repr>()?;
+        input.parse::<Token![=]>()?;
+        let lit: LitStr = input.parse()?;
+        match lit.value().as_str() {
+            "flat" => Ok(Self::Flat),
+            "structured" => Ok(Self::Structured),
+            _ => Err(Error::new(
+                lit.span(),
+                "Expected \"flat\" or \"structured\"",
+            )),
+        }
+    }
+}
+
+/// Emits the copy of the flat enum named `ident` deriving `derive`, whose fields are wrapped
+/// with `wrap`.
+fn emit_mirror(
+    input: &ItemEnum,
+    serde: &Path,
+    ident: &Ident,
+    derive: &str,
+    generics: &Generics,
+    bound: &str,
+    wrap: impl Fn(&Type) -> TokenStream,
+) -> TokenStream {
+    quote! {
+        #[derive(#serde::#{Ident::new(derive, Span::call_site())})]
+        #[serde(
+            crate = #{quote!(#serde).to_string()},
+            rename = #{input.ident.to_string()},
+            bound = #bound,
+        )]
+        enum #ident #generics #{&generics.where_clause}
+        {
+            #(for variant in &input.variants) {
+                #{&variant.ident}
+                #(if let Fields::Named(fields) = &variant.fields) {
+                    { #(for field in &fields.named), { #{&field.ident}: #{wrap(&field.ty)} } }
+                }
+                #(if let Fields::Unnamed(fields) = &variant.fields) {
+                    ( #(for field in &fields.unnamed), { #{wrap(&field.ty)} } )
+                },
+            }
+        }
+    }
+}
+
+/// Externally tagged with the variant names of the flat enum. The enum is converted from or
+/// into the copy of the enum which derives `Serialize` and `Deserialize`, with the same bounds.
+fn emit_flat(input: &ItemEnum, serde: &Path) -> TokenStream {
+    let ident = &input.ident;
+    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
+    let mut field_types: Vec<&Type> = Vec::new();
+    for field in input.variants.iter().flat_map(|variant| &variant.fields) {
+        if !field_types.contains(&&field.ty) {
+            field_types.push(&field.ty);
+        }
+    }
+    let bound = |tr: TokenStream| -> String {
+        field_types
+            .iter()
+            .map(|ty| quote!(#ty: #serde::#tr).to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let where_clause = |tr: TokenStream| -> WhereClause {
+        let mut where_clause = g_where.cloned().unwrap_or_else(|| parse_quote!(where));
+        for ty in &field_types {
+            where_clause.predicates.push(parse_quote!(#ty: #serde::#tr));
+        }
+        where_clause
+    };
+    let ser = Ident::new("__FlatEnumSerialize", Span::call_site());
+    let de = Ident::new("__FlatEnumDeserialize", Span::call_site());
+    let ser_lifetime: Lifetime = parse_quote!('__flat_enum_ser);
+    let mut ser_generics = input.generics.clone();
+    ser_generics.params.insert(0, parse_quote!(#ser_lifetime));
+    let mut de_generics = input.generics.clone();
+    de_generics.params.insert(0, parse_quote!('de));
+    let (de_impl, _, _) = de_generics.split_for_impl();
+    let ser_args: Vec<_> = input
+        .generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Lifetime(lt) => {
+                let lt = &lt.lifetime;
+                quote!(#lt)
+            }
+            GenericParam::Type(tp) => {
+                let ident = &tp.ident;
+                quote!(#ident)
+            }
+            GenericParam::Const(cp) => {
+                let ident = &cp.ident;
+                quote!(#ident)
+            }
+        })
+        .collect();
+    quote! {
+        const _: () = {
+            #{emit_mirror(input, serde, &ser, "Serialize", &ser_generics, &bound(quote!(Serialize)), |ty| quote!(&#ser_lifetime #ty))}
+            #{emit_mirror(input, serde, &de, "Deserialize", &input.generics, &bound(quote!(Deserialize<'de>)), |ty| quote!(#ty))}
+
+            #[automatically_derived]
+            impl #g_impl #serde::Serialize for #ident #g_type
+            #{where_clause(quote!(Serialize))}
+            {
+                fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+                where
+                    S: #serde::Serializer,
+                {
+                    #(if input.variants.is_empty()) {
+                        let _ = serializer;
+                        match *self {}
+                    }
+                    #(else) {
+                        let value: #ser<'_, #(#ser_args),*> = match self {
+                            #(for variant in &input.variants) {
+                                #(let ids = field_ids(variant, "a")) {
+                                    #{emit_pattern(&quote!(Self), variant, &ids)}
+                                        => #{emit_pattern(&quote!(#ser), variant, &ids)},
+                                }
+                            }
+                        };
+                        #serde::Serialize::serialize(&value, serializer)
+                    }
+                }
+            }
+
+            #[automatically_derived]
+            impl #de_impl #serde::Deserialize<'de> for #ident #g_type
+            #{where_clause(quote!(Deserialize<'de>))}
+            {
+                fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+                where
+                    D: #serde::Deserializer<'de>,
+                {
+                    ::core::result::Result::Ok(
+                        match <#de #g_type as #serde::Deserialize<'de>>::deserialize(deserializer)? {
+                            #(for variant in &input.variants) {
+                                #(let ids = field_ids(variant, "a")) {
+                                    #{emit_pattern(&quote!(#de), variant, &ids)}
+                                        => #{emit_pattern(&quote!(Self), variant, &ids)},
+                                }
+                            }
+                        }
+                    )
+                }
+            }
+        };
+    }
+}
+
+/// Emits the struct named `ident` holding the references to the fields of `variant`, which is
+/// serialized as the variant `index` of the enum `name`, like the derived `Serialize`.
+fn emit_variant_ref(
+    serde: &Path,
+    ident: &Ident,
+    name: &Ident,
+    index: usize,
+    variant: &Variant,
+) -> TokenStream {
+    let ids = field_ids(variant, "a");
+    let params: Vec<_> = (0..ids.len())
+        .map(|i| Ident::new(&format!("F{}", i), Span::call_site()))
+        .collect();
+    let indices = (0..ids.len()).map(Index::from);
+    let args = quote! {
+        #{name.to_string()},
+        #{Literal::u32_unsuffixed(index as u32)},
+        #{variant.ident.to_string()},
+    };
+    let body = match &variant.fields {
+        Fields::Unit => quote! { serializer.serialize_unit_variant(#args) },
+        Fields::Unnamed(_) if ids.len() == 1 => {
+            quote! { serializer.serialize_newtype_variant(#args self.0) }
+        }
+        Fields::Unnamed(_) => quote! {
+            let mut state = serializer.serialize_tuple_variant(#args #{ids.len()})?;
+            #(for i in indices) {
+                #serde::ser::SerializeTupleVariant::serialize_field(&mut state, self.#i)?;
+            }
+            #serde::ser::SerializeTupleVariant::end(state)
+        },
+        Fields::Named(fields) => quote! {
+            let mut state = serializer.serialize_struct_variant(#args #{ids.len()})?;
+            #(for (field, i) in fields.named.iter().zip(indices)) {
+                #serde::ser::SerializeStructVariant::serialize_field(
+                    &mut state,
+                    #{field.ident.as_ref().unwrap().to_string()},
+                    self.#i,
+                )?;
+            }
+            #serde::ser::SerializeStructVariant::end(state)
+        },
+    };
+    quote! {
+        #(if params.is_empty()) {
+            struct #ident;
+            impl #serde::Serialize for #ident
+        }
+        #(else) {
+            struct #ident<'a, #(#params),*>(#(&'a #params),*);
+            impl<#(#params: #serde::Serialize),*> #serde::Serialize for #ident<'_, #(#params),*>
+        }
+        {
+            fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+            where
+                S: #serde::Serializer,
+            {
+                #body
+            }
+        }
+    }
+}
+
+/// The same as the structured enum. `Serialize` writes the externally tagged variants of the
+/// enums in the origin of each variant, and `Deserialize` goes through `IntoFlat::into_flat()`.
+fn emit_structured(
+    input: &ItemEnum,
+    krate: &Path,
+    structured: &Path,
+    serde: &Path,
+    origins: &[Origin],
+) -> TokenStream {
+    let ident = &input.ident;
+    let (g_impl, g_type, _) = input.generics.split_for_impl();
+    let ser_generics = add_bounds(
+        &input.generics,
+        field_types(input),
+        &parse_quote!(#serde::Serialize),
+    );
+    let mut de_generics = input.generics.clone();
+    de_generics.params.insert(0, parse_quote!('de));
+    de_generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote!(#structured: #serde::Deserialize<'de>));
+    let (de_impl, _, de_where) = de_generics.split_for_impl();
+    let refs: Vec<_> = (0..input.variants.len())
+        .map(|i| Ident::new(&format!("__FlatEnumVariant{}", i), Span::call_site()))
+        .collect();
+    quote! {
+        #[automatically_derived]
+        impl #g_impl #serde::Serialize for #ident #g_type
+        #{ser_generics.where_clause.as_ref()}
+        {
+            fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+            where
+                S: #serde::Serializer,
+            {
+                #(if input.variants.is_empty()) {
+                    let _ = serializer;
+                    match *self {}
+                }
+                #(else) {
+                    #(for ((variant, origin), r) in input.variants.iter().zip(origins).zip(&refs)) {
+                        #(let level = origin.levels.last().unwrap()) {
+                            #{emit_variant_ref(serde, r, &level.name, level.index, variant)}
+                        }
+                    }
+                    match self {
+                        #(for ((variant, origin), r) in input.variants.iter().zip(origins).zip(&refs)) {
+                            #(let ids = field_ids(variant, "a")) {
+                                #{emit_pattern(&quote!(Self), variant, &ids)} => {
+                                    #serde::Serialize::serialize(
+                                        &#krate::__private::SerializeNested {
+                                            levels: &[
+                                                #(for (level, group) in origin.levels.iter().zip(&origin.groups)) {
+                                                    (
+                                                        #{level.name.to_string()},
+                                                        #{Literal::u32_unsuffixed(level.index as u32)},
+                                                        #{group.to_string()},
+                                                    ),
+                                                }
+                                            ],
+                                            value: &#r #(if !ids.is_empty()) { (#(#ids),*) },
+                                        },
+                                        serializer,
+                                    )
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        #[automatically_derived]
+        impl #de_impl #serde::Deserialize<'de> for #ident #g_type #de_where {
+            fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+            where
+                D: #serde::Deserializer<'de>,
+            {
+                <#structured as #serde::Deserialize<'de>>::deserialize(deserializer)
+                    .map(#krate::IntoFlat::into_flat)
+            }
+        }
+    }
+}
+
+/// Implements `Serialize` and `Deserialize` on the flat enum in the representation `repr`.
+pub fn emit(
+    input: &ItemEnum,
+    krate: &Path,
+    structured: &Path,
+    origins: &[Origin],
+    repr: Repr,
+) -> TokenStream {
+    let serde: Path = parse_quote!(#krate::__private::serde);
+    match repr {
+        Repr::Flat => emit_flat(input, &serde),
+        Repr::Structured => emit_structured(input, krate, structured, &serde, origins),
+    }
+}
@@ -1,17 +1,152 @@
+use proc_macro2::{Literal, Span, TokenStream};
 use proc_macro_error::abort;
 use syn::punctuated::{Pair, Punctuated};
 use syn::spanned::Spanned;
 use syn::*;
-use template_quote::quote;
+use template_quote::{quote, ToTokens};
 
-pub fn getrandom() -> u64 {
-    use core::hash::{BuildHasher, Hash, Hasher};
-    use proc_macro2::Span;
-    use std::collections::hash_map::RandomState;
+/// Returns the hash used in the names of the generated modules and macros, like
+/// `flat_enum_macro_{hash}_{ident}`.
+///
+/// Since `#[macro_export]` puts the macros in the crate root, the hash is derived from the crate
+/// name, the location of `ident` and the tokens of `item`, so that the items of the same name in
+/// different modules do not conflict, while the expansion is reproducible. The location is only
+/// available on Rust 1.88 or later, where the identical items conflict only if they are expanded
+/// from the same place in a `macro_rules!` body. On the older toolchains, the identical items of
+/// the same name in a crate get the same names, which are reported as defined multiple times at
+/// `ident`.
+pub fn item_hash(ident: &Ident, item: &impl ToTokens) -> u64 {
+    let crate_name = std::env::var("CARGO_CRATE_NAME")
+        .or_else(|_| std::env::var("CARGO_PKG_NAME"))
+        .unwrap_or_default();
+    // The build script enables it only on Rust 1.88 or later.
+    #[cfg(flat_enum_span_location)]
+    #[allow(clippy::incompatible_msrv)]
+    let location = {
+        let span = ident.span().unwrap();
+        format!("{}:{}:{}", span.file(), span.line(), span.column())
+    };
+    #[cfg(not(flat_enum_span_location))]
+    let location = "";
+    let seed = format!(
+        "{}\0{}\0{}\0{}",
+        crate_name,
+        location,
+        ident,
+        item.to_token_stream()
+    );
+    seed.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Emits `path` in the helper macro, where the leading `crate` is replaced with `$crate`, so that
+/// the path is resolved in the crate defining the macro.
+pub fn path_in_macro(path: &Path) -> TokenStream {
+    match path.segments.first() {
+        Some(first) if path.leading_colon.is_none() && first.ident == "crate" => {
+            let rest = path.segments.iter().skip(1);
+            quote!($crate #(:: #rest)*)
+        }
+        _ => path.to_token_stream(),
+    }
+}
+
+/// Version of the protocol between the helper macros, which is increased when the rules change
+/// incompatibly. The helper macros accept only the protocol they are defined with, since the
+/// rules of the older protocols are not kept.
+const PROTOCOL: u32 = 6;
+
+/// Emits the header passed to the helper macros, like `flat_enum = "0.1.1", protocol = 1,`,
+/// which follows the command like `@emit_enum`.
+pub fn protocol_header() -> TokenStream {
+    quote! {
+        flat_enum = #{env!("CARGO_PKG_VERSION")},
+        protocol = #{Literal::u32_unsuffixed(PROTOCOL)},
+    }
+}
 
-    let mut hasher = RandomState::new().build_hasher();
-    format!("{:?}", &Span::call_site()).hash(&mut hasher);
-    hasher.finish()
+/// Emits the rules reporting the incompatible header with `compile_error!`, which are placed
+/// after the other rules.
+fn emit_protocol_fallback(ident: &Ident) -> TokenStream {
+    let message = format!(
+        "`{}` is defined with flat_enum {} (protocol {}), which is incompatible with",
+        ident,
+        env!("CARGO_PKG_VERSION"),
+        PROTOCOL
+    );
+    let help = "; use the compatible versions of flat_enum in the crates";
+    quote! {
+        (@$_command:ident flat_enum = $version:literal, protocol = $protocol:literal, $($_:tt)*) => {
+            ::core::compile_error!(::core::concat!(
+                #message, " flat_enum ", $version, " (protocol ", ::core::stringify!($protocol), ") used here", #help
+            ));
+        };
+        (@$_command:ident flat_enum = $version:literal, $($_:tt)*) => {
+            ::core::compile_error!(::core::concat!(#message, " flat_enum ", $version, " used here", #help));
+        };
+        ($($_:tt)*) => {
+            ::core::compile_error!(#{format!("{} the flat_enum used here{}", message, help)});
+        };
+    }
+}
+
+/// Emits the helper macro in a hidden module, and re-exports it as `ident` with `vis`, so that the
+/// macro is found by the same path as the item.
+///
+/// `rules` emits the rules of the macro given the matcher of the header. The rules called with the
+/// incompatible header are reported with `compile_error!`.
+///
+/// The macro is `#[macro_export]`ed only if the item is `pub`, so that it can be used from other
+/// crates. Otherwise it is visible only in the crate, and does not appear in the crate root.
+///
+/// If `local` is specified, the macro is just the textually scoped `macro_rules!` named `ident`,
+/// which can be defined in functions and blocks.
+pub fn emit_helper_macro(
+    ident: &Ident,
+    vis: &Visibility,
+    item: &impl ToTokens,
+    rules: impl Fn(&TokenStream) -> TokenStream,
+    local: bool,
+) -> TokenStream {
+    let rules = quote! {
+        #{rules(&quote! {
+            flat_enum = $_version:literal,
+            protocol = #{Literal::u32_unsuffixed(PROTOCOL)},
+        })}
+        #{emit_protocol_fallback(ident)}
+    };
+    if local {
+        return quote! {
+            #[allow(unused_macros)]
+            macro_rules! #ident { #rules }
+        };
+    }
+    let hash = item_hash(ident, item);
+    // Spanned at `ident`, so that the collision is reported at the item.
+    let module_ident = Ident::new(
+        &format!("flat_enum_module_{:x}_{}", hash, ident),
+        ident.span(),
+    );
+    let macro_ident = Ident::new(
+        &format!("flat_enum_macro_{:x}_{}", hash, ident),
+        ident.span(),
+    );
+    let exported = matches!(vis, Visibility::Public(_));
+    quote! {
+        #[doc(hidden)]
+        #[allow(non_snake_case)]
+        mod #module_ident {
+            #(if exported) { #[macro_export] }
+            #[doc(hidden)]
+            macro_rules! #macro_ident { #rules }
+            #[doc(hidden)]
+            #[allow(unused)]
+            #(if exported) { pub } #(else) { pub(crate) } use #macro_ident as #ident;
+        }
+        #[allow(unused)]
+        #vis use #module_ident::*;
+    }
 }
 
 pub fn generics_remove_defaults(generics: &Generics) -> Generics {
@@ -58,28 +193,212 @@ pub fn generics_to_arguments(generics: &Generics) -> PathArguments {
             })),
         };
         args.push_value(v);
-        match p {
-            Pair::Punctuated(_, p) => args.push_punct(p.clone()),
-            _ => (),
+        if let Pair::Punctuated(_, p) = p {
+            args.push_punct(*p);
         }
     }
     PathArguments::AngleBracketed(AngleBracketedGenericArguments {
         colon2_token: None,
-        lt_token: generics.lt_token.unwrap_or(Default::default()),
+        lt_token: generics.lt_token.unwrap_or_default(),
         args,
-        gt_token: generics.gt_token.unwrap_or(Default::default()),
+        gt_token: generics.gt_token.unwrap_or_default(),
+    })
+}
+
+fn unit_type() -> Type {
+    Type::Tuple(TypeTuple {
+        paren_token: Default::default(),
+        elems: Punctuated::new(),
     })
 }
 
 pub fn generic_arg_to_type(arg: GenericArgument) -> Type {
     match arg {
-        GenericArgument::Lifetime(lt) => {
-            syn::parse(quote! {& #lt ()}.into()).expect("Cannot parse reference to type")
-        }
+        GenericArgument::Lifetime(lt) => Type::Reference(TypeReference {
+            and_token: Default::default(),
+            lifetime: Some(lt),
+            mutability: None,
+            elem: Box::new(unit_type()),
+        }),
         GenericArgument::Type(ty) => ty,
-        GenericArgument::Const(expr) => {
-            syn::parse(quote! {[(); #expr]}.into()).expect("Cannot parse array to type")
+        GenericArgument::Const(expr) => Type::Array(TypeArray {
+            bracket_token: Default::default(),
+            elem: Box::new(unit_type()),
+            semi_token: Default::default(),
+            len: expr,
+        }),
+        _ => abort!(
+            arg.span(),
+            "associated type bindings and constraints are not supported here";
+            help = "Specify the generic arguments of the enum, like `Enum1<A, 'a, N>`"
+        ),
+    }
+}
+
+pub fn to_snake_case(ident: &Ident) -> String {
+    let s = ident.to_string();
+    let chars: Vec<char> = s.trim_start_matches("r#").chars().collect();
+    let mut ret = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let prev_lower = i > 0 && (chars[i - 1].is_lowercase() || chars[i - 1].is_numeric());
+            let next_lower = i > 0
+                && chars[i - 1].is_uppercase()
+                && matches!(chars.get(i + 1), Some(c) if c.is_lowercase());
+            if prev_lower || next_lower {
+                ret.push('_');
+            }
+            ret.extend(c.to_lowercase());
+        } else {
+            ret.push(*c);
+        }
+    }
+    ret
+}
+
+/// Removes the trait named `name` from `#[derive(..)]` attributes. Returns `true` if found.
+pub fn remove_derive(attrs: &mut Vec<Attribute>, name: &str) -> bool {
+    let mut found = false;
+    attrs.retain_mut(|attr| {
+        if !attr.path.is_ident("derive") {
+            return true;
+        }
+        let Ok(paths) = attr.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)
+        else {
+            return true;
+        };
+        let len = paths.len();
+        let paths: Punctuated<Path, Token![,]> = paths
+            .into_iter()
+            .filter(|path| !matches!(path.segments.last(), Some(seg) if seg.ident == name))
+            .collect();
+        if paths.len() == len {
+            return true;
+        }
+        found = true;
+        attr.tokens = quote! { (#paths) };
+        !paths.is_empty()
+    });
+    found
+}
+
+/// Formats tokens like the source code, e.g. `Vec<&'a str>` instead of `Vec < & 'a str >`.
+pub fn tokens_to_string(tokens: proc_macro2::TokenStream) -> String {
+    use proc_macro2::{Delimiter, Spacing, TokenTree};
+    fn walk(tokens: proc_macro2::TokenStream, out: &mut String, word: &mut bool) {
+        for tt in tokens {
+            match tt {
+                TokenTree::Group(g) => {
+                    let (open, close) = match g.delimiter() {
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Brace => ("{", "}"),
+                        Delimiter::Bracket => ("[", "]"),
+                        Delimiter::None => ("", ""),
+                    };
+                    out.push_str(open);
+                    *word = false;
+                    walk(g.stream(), out, word);
+                    out.push_str(close);
+                    *word = true;
+                }
+                TokenTree::Ident(_) | TokenTree::Literal(_) => {
+                    if *word {
+                        out.push(' ');
+                    }
+                    out.push_str(&tt.to_string());
+                    *word = true;
+                }
+                TokenTree::Punct(p) => {
+                    match p.as_char() {
+                        ',' | ';' => out.push_str(&format!("{} ", p.as_char())),
+                        '-' if p.spacing() == Spacing::Joint => out.push_str(" -"),
+                        '>' if out.ends_with(" -") => out.push_str("> "),
+                        '=' => out.push_str(" = "),
+                        c => out.push(c),
+                    }
+                    *word = false;
+                }
+            }
+        }
+    }
+    let mut ret = String::new();
+    walk(tokens, &mut ret, &mut false);
+    ret.trim().to_owned()
+}
+
+/// Returns `generics` with where predicates `ty: bound` for each type in `tys`.
+pub fn add_bounds<'a>(
+    generics: &Generics,
+    tys: impl IntoIterator<Item = &'a Type>,
+    bound: &TypeParamBound,
+) -> Generics {
+    let mut ret = generics.clone();
+    let mut seen = std::collections::HashSet::new();
+    let where_clause = ret.make_where_clause();
+    for ty in tys {
+        if seen.insert(ty) {
+            where_clause.predicates.push(parse_quote!(#ty: #bound));
+        }
+    }
+    ret
+}
+
+/// Returns the types of the fields of all variants.
+pub fn field_types(input: &ItemEnum) -> Vec<&Type> {
+    input
+        .variants
+        .iter()
+        .flat_map(|variant| variant.fields.iter().map(|field| &field.ty))
+        .collect()
+}
+
+/// Returns the path of the enum type without the generic arguments, like `Enum` for
+/// `Enum<'a, A>`. It is used as the prefix of variants in patterns and struct expressions, where
+/// the arguments are inferred. Turbofish cannot be used, because `Enum::<'a>::V { .. }` is
+/// rejected.
+pub fn type_to_enum_path(ty: &Type) -> Path {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => path_without_arguments(path),
+        Type::Group(TypeGroup { elem, .. }) | Type::Paren(TypeParen { elem, .. }) => {
+            type_to_enum_path(elem)
+        }
+        _ => abort!(ty.span(), "Expected path to the enum"),
+    }
+}
+
+/// Removes the generic arguments from `path`.
+pub fn path_without_arguments(path: &Path) -> Path {
+    let mut path = path.clone();
+    for seg in path.segments.iter_mut() {
+        seg.arguments = PathArguments::None;
+    }
+    path
+}
+
+/// Removes the invisible groups around the type, which are made by `$ty` in `macro_rules!`.
+pub fn ungroup(ty: &Type) -> &Type {
+    match ty {
+        Type::Group(TypeGroup { elem, .. }) => ungroup(elem),
+        _ => ty,
+    }
+}
+
+/// Returns the identifiers bound to the fields of `variant`, like `a0`, `a1`, .. for `prefix`
+/// `a`.
+pub fn field_ids(variant: &Variant, prefix: &str) -> Vec<Ident> {
+    (0..variant.fields.len())
+        .map(|i| Ident::new(&format!("{}{}", prefix, i), Span::call_site()))
+        .collect()
+}
+
+/// Emits the pattern or the expression of `variant` in `path`, with `ids` as the fields.
+pub fn emit_pattern<T: ToTokens>(path: &TokenStream, variant: &Variant, ids: &[T]) -> TokenStream {
+    match &variant.fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote! { #path :: #{&variant.ident} { #(#names: #ids),* } }
         }
-        _ => abort!(arg.span(), "Not supported"),
+        Fields::Unnamed(_) => quote! { #path :: #{&variant.ident} ( #(#ids),* ) },
+        Fields::Unit => quote! { #path :: #{&variant.ident} },
     }
 }
@@ -0,0 +1,84 @@
+use crate::origin::Origin;
+use proc_macro2::TokenStream;
+use syn::*;
+use template_quote::quote;
+
+/// Implements `VariantInfo`. `origins` is specified on flat enums, which provides
+/// `origin_path()`.
+pub fn emit(input: &ItemEnum, krate: &Path, origins: Option<&[Origin]>) -> TokenStream {
+    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
+    quote! {
+        #[automatically_derived]
+        impl #g_impl #krate::VariantInfo for #{&input.ident} #g_type #g_where {
+            const VARIANT_NAMES: &'static [&'static str] = &[
+                #(for variant in &input.variants) {
+                    #{variant.ident.to_string()},
+                }
+            ];
+
+            fn variant_index(&self) -> usize {
+                #(if input.variants.is_empty()) {
+                    match *self {}
+                }
+                #(else) {
+                    match self {
+                        #(for (i, variant) in input.variants.iter().enumerate()) {
+                            Self::#{&variant.ident} { .. } => #i,
+                        }
+                    }
+                }
+            }
+
+            #(if let Some(origins) = origins) {
+                fn origin_path(&self) -> &'static [&'static str] {
+                    #(if input.variants.is_empty()) {
+                        match *self {}
+                    }
+                    #(else) {
+                        match self {
+                            #(for (variant, origin) in input.variants.iter().zip(origins)) {
+                                Self::#{&variant.ident} { .. } => &[
+                                    #(for name in origin.path(&variant.ident)) { #name, }
+                                ],
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Emits `stable_id()` and `from_stable_id()` on the flat enum, if the variants have the stable
+/// IDs.
+pub fn emit_stable_id(input: &ItemEnum, origins: &[Origin]) -> TokenStream {
+    if input.variants.is_empty() || origins.iter().any(|origin| origin.id.is_none()) {
+        return TokenStream::new();
+    }
+    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
+    quote! {
+        #[automatically_derived]
+        impl #g_impl #{&input.ident} #g_type #g_where {
+            /// Returns the stable ID of the variant, given with `#[flat_enum(id = N)]` and
+            /// `#[flatten(id_base = N)]`.
+            #{&input.vis} fn stable_id(&self) -> u32 {
+                match self {
+                    #(for (variant, origin) in input.variants.iter().zip(origins)) {
+                        Self::#{&variant.ident} { .. } => #{origin.id.as_ref().unwrap()},
+                    }
+                }
+            }
+
+            /// Returns the index of the variant which has the stable ID `id`, like
+            /// `VariantInfo::variant_index()`.
+            #{&input.vis} fn from_stable_id(id: u32) -> Option<usize> {
+                match id {
+                    #(for (i, origin) in origins.iter().enumerate()) {
+                        #{origin.id.as_ref().unwrap()} => Some(#i),
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+}
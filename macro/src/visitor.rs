@@ -0,0 +1,95 @@
+use crate::origin::Origin;
+use crate::util::{emit_pattern, field_ids, to_snake_case};
+use proc_macro2::TokenStream;
+use proc_macro_error::abort;
+use syn::spanned::Spanned;
+use syn::*;
+use template_quote::quote;
+
+fn visitor_ident(ident: &Ident) -> Ident {
+    Ident::new(&format!("{}Visitor", ident), ident.span())
+}
+
+fn visit_ident(variant: &Variant) -> Ident {
+    Ident::new(
+        &format!("visit_{}", to_snake_case(&variant.ident)),
+        variant.ident.span(),
+    )
+}
+
+/// Converts the type of the flattened enum like `Enum1<A>` into its visitor like
+/// `Enum1Visitor<A>`.
+fn visitor_path(ty: &Type) -> Path {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) if !path.segments.is_empty() => {
+            let mut path = path.clone();
+            let seg = path.segments.last_mut().unwrap();
+            seg.ident = visitor_ident(&seg.ident);
+            path
+        }
+        Type::Group(TypeGroup { elem, .. }) | Type::Paren(TypeParen { elem, .. }) => {
+            visitor_path(elem)
+        }
+        _ => abort!(ty.span(), "Cannot find the visitor of the type"),
+    }
+}
+
+/// Emits the visitor trait, which has a `visit_*` method for each variant, and `accept()` on
+/// the enum. Variants with the origin are visited with the visitor of the outermost flattened
+/// enum, which becomes the supertrait.
+pub fn emit(input: &ItemEnum, origins: &[Origin]) -> TokenStream {
+    let ident = &input.ident;
+    let visitor = visitor_ident(ident);
+    let (g_impl, g_type, g_where) = input.generics.split_for_impl();
+    let mut supertraits: Vec<Path> = Vec::new();
+    for origin in origins {
+        if let Some(ty) = &origin.ty {
+            let path = visitor_path(ty);
+            if !supertraits.contains(&path) {
+                supertraits.push(path);
+            }
+        }
+    }
+    quote! {
+        #[doc = #{format!("Visitor of [`{}`], which has a method for each variant.", ident)}]
+        #[allow(clippy::ptr_arg)]
+        #{&input.vis} trait #visitor #g_impl
+        #(if !supertraits.is_empty()) {
+            : #(#supertraits)+*
+        }
+        #g_where
+        {
+            #(for (variant, origin) in input.variants.iter().zip(origins)) {
+                #(if origin.ty.is_none()) {
+                    #[doc = #{format!("Visits `{}::{}`.", ident, &variant.ident)}]
+                    fn #{visit_ident(variant)}(
+                        &mut self,
+                        #(for (field, id) in variant.fields.iter().zip(field_ids(variant, "a"))) {
+                            #{field.ident.as_ref().unwrap_or(&id)}: &#{&field.ty},
+                        }
+                    );
+                }
+            }
+        }
+
+        #[automatically_derived]
+        impl #g_impl #ident #g_type #g_where {
+            #[doc = #{format!("Calls the method of `{}` corresponding to the variant.", visitor)}]
+            #{&input.vis} fn accept(&self, visitor: &mut (impl #visitor #g_type + ?Sized)) {
+                #(if input.variants.is_empty()) {
+                    let _ = visitor;
+                    match *self {}
+                }
+                #(else) {
+                    match self {
+                        #(for variant in &input.variants) {
+                            #(let ids = field_ids(variant, "a")) {
+                                #{emit_pattern(&quote!(Self), variant, &ids)} => visitor.#{visit_ident(variant)}(#(#ids),*),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,13 @@
+//! Facade which re-exports `flat_enum`, like the crates wrapping it for their users.
+
+pub use flat_enum;
+
+use flat_enum::FlatTarget;
+
+/// Flattened in `test_flat_enum_facade_user`. The helper macro carries the path of the crate.
+#[derive(FlatTarget, Clone, Debug, PartialEq)]
+#[flat_enum(crate = crate::flat_enum, visitor)]
+pub enum Shape<A> {
+    Circle(A),
+    Square { side: A },
+}
@@ -0,0 +1 @@
+//! Uses `flat_enum` only through `test_flat_enum_facade`. The tests are in `tests/`.
@@ -0,0 +1,77 @@
+//! `flat_enum` used only through the facade crate, which this crate depends on instead of
+//! `flat_enum`.
+
+use test_flat_enum_facade::flat_enum::{
+    delegatable, flat, into_flat, FlatTarget, IntoFlat, VariantInfo,
+};
+use test_flat_enum_facade::{Shape, ShapeVisitor};
+
+#[delegatable(crate = ::test_flat_enum_facade::flat_enum)]
+pub trait Area {
+    fn area(&self) -> u32;
+}
+
+impl Area for u32 {
+    fn area(&self) -> u32 {
+        *self
+    }
+}
+
+#[derive(FlatTarget, Clone, Debug, PartialEq)]
+#[flat_enum(crate = ::test_flat_enum_facade::flat_enum, visitor, codec)]
+pub enum Line {
+    Segment(u32),
+    Point,
+}
+
+#[into_flat(FigureFlat)]
+#[flat_enum(crate = ::test_flat_enum_facade::flat_enum)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Figure {
+    #[flatten]
+    Shape(Shape<u32>),
+    #[flatten]
+    Line(Line),
+    Text(u32),
+}
+
+/// The path of the crate is taken from `Figure`.
+#[flat(
+    Figure,
+    accessors,
+    debug = "structured",
+    structured(PartialEq, Eq, PartialOrd, Ord, Hash),
+    visitor,
+    codec,
+    ffi
+)]
+#[derive(Clone, Copy)]
+pub enum FigureFlat {}
+
+#[into_flat(MappedFlat<A>)]
+#[flat_enum(crate = ::test_flat_enum_facade::flat_enum)]
+pub enum Mapped<A> {
+    #[flatten]
+    Shape(Shape<A>),
+    Other(A),
+}
+
+#[flat(Mapped<A>, map(A), delegate(Area))]
+pub enum MappedFlat<A> {}
+
+#[test]
+fn test_facade_crate() {
+    let v = Figure::Shape(Shape::Square { side: 3 }).into_flat();
+    assert_eq!(v.variant_name(), "Square");
+    assert_eq!(v.as_square(), Some((&3,)));
+    assert_eq!(
+        format!("{:?}", v),
+        "Figure::Shape(Shape::Square { side: 3 })"
+    );
+    assert!(FigureFlat::Circle(1) < FigureFlat::Point);
+    assert_eq!(
+        Figure::from_flat(v),
+        Figure::Shape(Shape::Square { side: 3 })
+    );
+    assert_eq!(MappedFlat::Circle(2u32).map(|a| a * 2).area(), 4);
+}
@@ -0,0 +1,46 @@
+use flat_enum::{flat, into_flat, FlatTarget};
+
+#[derive(FlatTarget)]
+pub enum Enum1<A> {
+    E1(A),
+    E2(),
+    E3(String, u8),
+}
+
+#[into_flat(Enum2Flat<A>)]
+pub enum Enum2<A> {
+    #[flatten]
+    Enum1(Enum1<A>),
+    E4,
+    MyVariant {
+        a: A,
+        b: u32,
+    },
+}
+
+#[flat(Enum2<A>, accessors)]
+#[derive(Debug, PartialEq)]
+pub enum Enum2Flat<A> {}
+
+#[test]
+fn test_accessors() {
+    let mut v = Enum2Flat::<()>::E3("abc".to_owned(), 3u8);
+    assert!(v.is_e3());
+    assert!(!v.is_e1());
+    assert_eq!(v.as_e3(), Some((&"abc".to_owned(), &3)));
+    assert_eq!(v.as_e1(), None);
+    if let Some((_, n)) = v.as_e3_mut() {
+        *n = 4;
+    }
+    assert_eq!(v.into_e3(), Ok(("abc".to_owned(), 4)));
+
+    let v: Enum2Flat<u8> = Enum2Flat::E4;
+    assert_eq!(v.as_e4(), Some(()));
+    assert_eq!(v.as_e2(), None);
+    assert_eq!(v.into_e1(), Err(Enum2Flat::E4));
+
+    let v: Enum2Flat<u8> = Enum2Flat::MyVariant { a: 1, b: 2 };
+    assert!(v.is_my_variant());
+    assert_eq!(v.as_my_variant(), Some((&1, &2)));
+    assert_eq!(v.into_my_variant(), Ok((1, 2)));
+}
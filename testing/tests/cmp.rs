@@ -0,0 +1,111 @@
+use flat_enum::{flat, into_flat, testing::assert_consistent, FlatTarget, IntoFlat};
+use std::collections::{BTreeSet, HashSet};
+
+#[derive(FlatTarget, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Enum1<A> {
+    E1(A),
+    E2(),
+    E3(String, A),
+}
+
+#[derive(FlatTarget, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Enum2 {
+    E4 { x: u8 },
+}
+
+#[into_flat(Enum3Flat<A>)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Enum3<A> {
+    E5,
+    #[flatten]
+    Enum1(Enum1<A>),
+    E6(A),
+    #[flatten]
+    Enum2(Enum2),
+}
+
+#[flat(Enum3<A>, structured(PartialEq, Eq, PartialOrd, Ord, Hash))]
+#[derive(FlatTarget, Clone, Debug)]
+pub enum Enum3Flat<A> {}
+
+/// xorshift64, to generate the values deterministically.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+
+    fn gen(&mut self) -> Enum3<u8> {
+        let a = self.next(3) as u8;
+        match self.next(6) {
+            0 => Enum3::E5,
+            1 => Enum3::Enum1(Enum1::E1(a)),
+            2 => Enum3::Enum1(Enum1::E2()),
+            3 => Enum3::Enum1(Enum1::E3(
+                ["", "a", "b"][self.next(3) as usize].to_owned(),
+                a,
+            )),
+            4 => Enum3::E6(a),
+            _ => Enum3::Enum2(Enum2::E4 { x: a }),
+        }
+    }
+}
+
+#[test]
+fn test_consistent() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for _ in 0..20 {
+        let values: Vec<_> = (0..30).map(|_| rng.gen()).collect();
+        assert_consistent(&values);
+    }
+}
+
+#[test]
+fn test_collections() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    let values: Vec<_> = (0..100).map(|_| rng.gen()).collect();
+    let structured: BTreeSet<_> = values.iter().cloned().collect();
+    let flat: BTreeSet<_> = values.iter().cloned().map(IntoFlat::into_flat).collect();
+    assert!(structured.iter().eq(flat.iter()));
+    let flat: HashSet<_> = values.iter().cloned().map(IntoFlat::into_flat).collect();
+    assert_eq!(structured.len(), flat.len());
+    assert!(flat.contains(&Enum3::E5.into_flat()));
+}
+
+#[derive(FlatTarget, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Empty {}
+
+/// The flat enum with `structured(..)` is flattened again, after the enum without variants.
+#[into_flat(Enum4Flat<A>)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Enum4<A> {
+    #[flatten]
+    Empty(Empty),
+    #[flatten]
+    Enum3(Enum3Flat<A>),
+    E7 { y: A },
+}
+
+#[flat(Enum4<A>, structured(PartialEq, Eq, PartialOrd, Ord, Hash))]
+#[derive(Clone, Debug)]
+pub enum Enum4Flat<A> {}
+
+#[test]
+fn test_consistent_nested() {
+    let mut rng = Rng(0x6a09_e667_f3bc_c908);
+    for _ in 0..20 {
+        let values: Vec<_> = (0..30)
+            .map(|_| match rng.next(4) {
+                0 => Enum4::E7 {
+                    y: rng.next(3) as u8,
+                },
+                _ => Enum4::Enum3(rng.gen().into_flat()),
+            })
+            .collect();
+        assert_consistent(&values);
+    }
+}
@@ -0,0 +1,105 @@
+use flat_enum::codec::{Decode, DecodeError, Encode};
+use flat_enum::{flat, into_flat, FlatTarget, IntoFlat};
+
+#[derive(FlatTarget, Clone, Debug, PartialEq)]
+#[flat_enum(codec)]
+pub enum Enum1<A> {
+    E1(A),
+    E2(),
+    E3(String, A),
+}
+
+#[derive(FlatTarget, Clone, Debug, PartialEq)]
+#[flat_enum(codec)]
+pub enum Enum2 {
+    E4 { x: u8, y: Vec<i32> },
+}
+
+#[into_flat(Enum3Flat<A>, codec)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Enum3<A> {
+    E5,
+    #[flatten]
+    Enum1(Enum1<A>),
+    E6([A; 2]),
+    #[flatten]
+    Enum2(Enum2),
+}
+
+#[flat(Enum3<A>, codec)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Enum3Flat<A> {}
+
+fn roundtrip<T: Encode + Decode + PartialEq + std::fmt::Debug>(value: &T) -> Vec<u8> {
+    let bytes = value.to_bytes();
+    assert_eq!(&T::from_bytes(&bytes).unwrap(), value);
+    bytes
+}
+
+#[test]
+fn test_codec_primitives() {
+    assert_eq!(roundtrip(&300u16), [0xac, 0x02]);
+    assert_eq!(roundtrip(&-1i32), [0x01]);
+    assert_eq!(roundtrip(&i64::MIN).len(), 10);
+    assert_eq!(roundtrip(&u128::MAX).len(), 19);
+    assert_eq!(roundtrip(&"ab".to_owned()), [2, b'a', b'b']);
+    assert_eq!(roundtrip(&vec![1u8, 2]), [2, 1, 2]);
+    assert_eq!(roundtrip(&[true, false]), [1, 0]);
+    roundtrip(&1.5f64);
+    roundtrip(&'あ');
+    assert_eq!(u8::from_bytes(&[0xac, 0x02]), Err(DecodeError::OutOfRange));
+    assert_eq!(u8::from_bytes(&[0x80]), Err(DecodeError::UnexpectedEnd));
+    assert_eq!(u8::from_bytes(&[1, 2]), Err(DecodeError::TrailingBytes));
+    assert_eq!(
+        String::from_bytes(&[1, 0xff]),
+        Err(DecodeError::InvalidUtf8)
+    );
+}
+
+#[test]
+fn test_codec_zero_sized() {
+    use flat_enum::codec::MAX_ZERO_SIZED_LEN;
+    assert_eq!(roundtrip(&vec![[0u8; 0]; 3]), [3]);
+    roundtrip(&vec![[0u8; 0]; MAX_ZERO_SIZED_LEN]);
+    let mut bytes = Vec::new();
+    flat_enum::codec::write_varint(&mut bytes, u64::MAX as u128);
+    assert_eq!(
+        Vec::<[u8; 0]>::from_bytes(&bytes),
+        Err(DecodeError::OutOfRange)
+    );
+}
+
+#[test]
+fn test_codec_enum() {
+    let values = [
+        Enum3::E5,
+        Enum3::Enum1(Enum1::E1(1u8)),
+        Enum3::Enum1(Enum1::E2()),
+        Enum3::Enum1(Enum1::E3("a".to_owned(), 2)),
+        Enum3::E6([3, 4]),
+        Enum3::Enum2(Enum2::E4 {
+            x: 5,
+            y: vec![-1, 2],
+        }),
+    ];
+    for value in values {
+        let structured = roundtrip(&value);
+        let flat = roundtrip(&value.clone().into_flat());
+        // The structured value has one more tag for the flattened enum.
+        let nested = matches!(value, Enum3::Enum1(_) | Enum3::Enum2(_));
+        assert_eq!(structured.len(), flat.len() + nested as usize);
+        assert_eq!(structured[structured.len() - flat.len() + 1..], flat[1..]);
+    }
+    assert_eq!(
+        Enum3Flat::E3("a".to_owned(), 2u8).to_bytes(),
+        [3, 1, b'a', 2]
+    );
+    assert_eq!(
+        Enum3::Enum1(Enum1::E3("a".to_owned(), 2u8)).to_bytes(),
+        [1, 2, 1, b'a', 2]
+    );
+    assert_eq!(
+        Enum3Flat::<u8>::from_bytes(&[7]),
+        Err(DecodeError::InvalidTag(7))
+    );
+}
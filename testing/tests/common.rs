@@ -0,0 +1,93 @@
+use flat_enum::{flat, into_flat, FlatTarget};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span(usize, usize);
+
+#[derive(FlatTarget)]
+pub enum Expr<A> {
+    Lit { value: A, span: Span },
+    Neg(Box<A>, Span),
+}
+
+#[derive(FlatTarget)]
+pub enum Stmt {
+    Let { name: String, span: Span },
+    Empty(usize, Span),
+}
+
+#[into_flat(NodeFlat<A>)]
+pub enum Node<A> {
+    #[flatten]
+    Expr(Expr<A>),
+    #[flatten]
+    Stmt(Stmt),
+    Item(String, Span),
+}
+
+#[flat(Node<A>, common(span = 1: Span))]
+pub enum NodeFlat<A> {}
+
+#[derive(FlatTarget)]
+pub enum Token {
+    Ident(usize, Span, String),
+    Punct { len: usize, span: Span },
+}
+
+#[into_flat(StmtOnlyFlat)]
+pub enum StmtOnly {
+    #[flatten]
+    Token(Token),
+    Block {
+        span: Span,
+        len: usize,
+    },
+    Empty(usize, Span),
+}
+
+#[flat(StmtOnly, common(span = 1: Span, len = 0: usize))]
+pub enum StmtOnlyFlat {}
+
+#[test]
+fn test_common() {
+    let mut values: Vec<NodeFlat<u8>> = vec![
+        NodeFlat::Lit {
+            value: 1,
+            span: Span(0, 1),
+        },
+        NodeFlat::Neg(Box::new(2), Span(1, 2)),
+        NodeFlat::Let {
+            name: "x".to_owned(),
+            span: Span(2, 3),
+        },
+        NodeFlat::Empty(0, Span(3, 4)),
+        NodeFlat::Item("f".to_owned(), Span(9, 9)),
+    ];
+    let spans: Vec<_> = values.iter().map(|value| *value.span()).collect();
+    assert_eq!(
+        spans,
+        [Span(0, 1), Span(1, 2), Span(2, 3), Span(3, 4), Span(9, 9)]
+    );
+    values[4].span_mut().0 = 4;
+    assert!(matches!(&values[4], NodeFlat::Item(_, Span(4, 9))));
+}
+
+#[test]
+fn test_common_multiple() {
+    let mut value = StmtOnlyFlat::Empty(3, Span(0, 1));
+    assert_eq!(*value.len(), 3);
+    *value.len_mut() += 1;
+    assert_eq!(*value.len(), 4);
+    assert_eq!(*value.span(), Span(0, 1));
+    let value = StmtOnlyFlat::Block {
+        span: Span(1, 2),
+        len: 5,
+    };
+    assert_eq!((*value.span(), *value.len()), (Span(1, 2), 5));
+    let value = StmtOnlyFlat::Ident(6, Span(2, 3), "x".to_owned());
+    assert_eq!((*value.span(), *value.len()), (Span(2, 3), 6));
+    let value = StmtOnlyFlat::Punct {
+        len: 7,
+        span: Span(3, 4),
+    };
+    assert_eq!((*value.span(), *value.len()), (Span(3, 4), 7));
+}
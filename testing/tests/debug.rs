@@ -0,0 +1,72 @@
+use flat_enum::{flat, into_flat, FlatTarget};
+
+#[derive(FlatTarget)]
+pub enum Enum1<A> {
+    E1(A),
+    E2(),
+    E3(String, u8),
+}
+
+#[derive(FlatTarget)]
+pub enum Enum2 {
+    E4,
+}
+
+#[into_flat(Enum3Flat<A>)]
+pub enum Enum3<A> {
+    #[flatten]
+    Enum1(Enum1<A>),
+    #[flatten]
+    Enum2(Enum2),
+    E5 {
+        x: A,
+        y: A,
+    },
+}
+
+#[flat(Enum3<A>, debug = "structured")]
+#[derive(FlatTarget)]
+pub enum Enum3Flat<A> {}
+
+#[into_flat(Enum4Flat<A>)]
+pub enum Enum4<A> {
+    #[flatten]
+    Enum3(Enum3Flat<A>),
+    E6,
+}
+
+#[flat(Enum4<A>, debug = "structured")]
+pub enum Enum4Flat<A> {}
+
+#[test]
+fn test_debug_structured() {
+    assert_eq!(
+        format!("{:?}", Enum3Flat::E1(5)),
+        "Enum3::Enum1(Enum1::E1(5))"
+    );
+    assert_eq!(
+        format!("{:?}", Enum3Flat::<u8>::E2()),
+        "Enum3::Enum1(Enum1::E2)"
+    );
+    assert_eq!(
+        format!("{:?}", Enum3Flat::<u8>::E3("abc".to_owned(), 1)),
+        "Enum3::Enum1(Enum1::E3(\"abc\", 1))"
+    );
+    assert_eq!(
+        format!("{:?}", Enum3Flat::<u8>::E4),
+        "Enum3::Enum2(Enum2::E4)"
+    );
+    assert_eq!(
+        format!("{:?}", Enum3Flat::E5 { x: 1, y: 2 }),
+        "Enum3::E5 { x: 1, y: 2 }"
+    );
+    assert_eq!(
+        format!("{:?}", Enum4Flat::E1(5)),
+        "Enum4::Enum3(Enum3Flat::Enum1(Enum1::E1(5)))"
+    );
+    assert_eq!(format!("{:?}", Enum4Flat::<u8>::E6), "Enum4::E6");
+    assert_eq!(
+        format!("{:#?}", Enum3Flat::E1(5)),
+        "Enum3::Enum1(\n    Enum1::E1(\n        5,\n    ),\n)"
+    );
+}
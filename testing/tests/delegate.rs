@@ -0,0 +1,140 @@
+use flat_enum::{delegatable, flat, into_flat, FlatTarget};
+
+#[delegatable]
+pub trait Handler {
+    fn name(&self) -> &str;
+    fn handle(&mut self, input: u32) -> u32;
+    fn into_total(self) -> u32;
+    fn describe(&self) -> String {
+        format!("handler {}", self.name())
+    }
+    fn create() -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+#[delegatable]
+pub trait Convert<T> {
+    fn convert<U: From<T>>(&self, value: T) -> (U, usize);
+}
+
+pub struct Counter(u32);
+
+impl Handler for Counter {
+    fn name(&self) -> &str {
+        "counter"
+    }
+    fn handle(&mut self, input: u32) -> u32 {
+        self.0 += input;
+        self.0
+    }
+    fn into_total(self) -> u32 {
+        self.0
+    }
+}
+
+impl<T> Convert<T> for Counter {
+    fn convert<U: From<T>>(&self, value: T) -> (U, usize) {
+        (value.into(), 1)
+    }
+}
+
+pub struct Named<'a> {
+    name: &'a str,
+}
+
+impl Handler for Named<'_> {
+    fn name(&self) -> &str {
+        self.name
+    }
+    fn handle(&mut self, input: u32) -> u32 {
+        input * 2
+    }
+    fn into_total(self) -> u32 {
+        self.name.len() as u32
+    }
+    fn describe(&self) -> String {
+        format!("named {}", self.name)
+    }
+}
+
+impl<T> Convert<T> for Named<'_> {
+    fn convert<U: From<T>>(&self, value: T) -> (U, usize) {
+        (value.into(), 2)
+    }
+}
+
+#[derive(FlatTarget)]
+pub enum Enum1<'a> {
+    Counter(Counter),
+    Label { named: Named<'a> },
+}
+
+#[into_flat(Enum2Flat<'a, A>)]
+pub enum Enum2<'a, A> {
+    #[flatten]
+    Enum1(Enum1<'a>),
+    Other(A),
+}
+
+#[flat(Enum2<'a, A>, delegate(Handler, Convert))]
+pub enum Enum2Flat<'a, A> {}
+
+impl Handler for bool {
+    fn name(&self) -> &str {
+        "bool"
+    }
+    fn handle(&mut self, input: u32) -> u32 {
+        *self = !*self;
+        input
+    }
+    fn into_total(self) -> u32 {
+        self as u32
+    }
+}
+
+impl<T> Convert<T> for bool {
+    fn convert<U: From<T>>(&self, value: T) -> (U, usize) {
+        (value.into(), 3)
+    }
+}
+
+#[test]
+fn test_delegate() {
+    let mut v: Enum2Flat<bool> = Enum2Flat::Counter(Counter(1));
+    assert_eq!(v.name(), "counter");
+    assert_eq!(v.handle(2), 3);
+    assert_eq!(v.handle(2), 5);
+    assert_eq!(v.describe(), "handler counter");
+    assert_eq!(v.into_total(), 5);
+
+    let mut v: Enum2Flat<bool> = Enum2Flat::Label {
+        named: Named { name: "abc" },
+    };
+    assert_eq!(v.name(), "abc");
+    assert_eq!(v.handle(2), 4);
+    assert_eq!(v.describe(), "named abc");
+    assert_eq!(v.into_total(), 3);
+
+    let mut v: Enum2Flat<bool> = Enum2Flat::Other(false);
+    assert_eq!(v.handle(7), 7);
+    assert!(matches!(v, Enum2Flat::Other(true)));
+    assert_eq!(v.into_total(), 1);
+
+    assert!(<Enum2Flat<bool> as Handler>::create().is_none());
+}
+
+#[test]
+fn test_delegate_generic() {
+    let v: Enum2Flat<bool> = Enum2Flat::Counter(Counter(1));
+    assert_eq!(v.convert::<u64>(3u32), (3u64, 1));
+    let v: Enum2Flat<bool> = Enum2Flat::Label {
+        named: Named { name: "abc" },
+    };
+    assert_eq!(v.convert::<i64>(3u8), (3i64, 2));
+    let v: Enum2Flat<bool> = Enum2Flat::Other(true);
+    assert_eq!(Convert::<u8>::convert::<u16>(&v, 3), (3u16, 3));
+}
@@ -0,0 +1,60 @@
+//! `flat_enum` used through the facade module.
+
+mod prelude {
+    pub use flat_enum;
+}
+
+mod upstream {
+    use crate::prelude::flat_enum::{delegatable, FlatTarget};
+
+    #[delegatable(crate = crate::prelude::flat_enum)]
+    pub trait Name {
+        fn name(&self) -> &'static str;
+    }
+
+    pub struct Foo;
+
+    impl Name for Foo {
+        fn name(&self) -> &'static str {
+            "foo"
+        }
+    }
+
+    #[derive(FlatTarget)]
+    #[flat_enum(crate = crate::prelude::flat_enum)]
+    pub enum Enum1<A> {
+        E1(A),
+        E2(Foo),
+    }
+}
+
+use prelude::flat_enum::{flat, into_flat, FlatTarget, IntoFlat, VariantInfo};
+use upstream::{Foo, Name};
+
+#[into_flat(Enum2Flat)]
+#[flat_enum(crate = crate::prelude::flat_enum)]
+pub enum Enum2 {
+    #[flatten]
+    Enum1(upstream::Enum1<&'static str>),
+    E3(Foo),
+}
+
+/// The path is taken from `Enum2`.
+#[flat(Enum2, delegate(Name))]
+#[derive(FlatTarget)]
+pub enum Enum2Flat {}
+
+impl Name for &'static str {
+    fn name(&self) -> &'static str {
+        self
+    }
+}
+
+#[test]
+fn test_facade() {
+    let v = Enum2::Enum1(upstream::Enum1::E2(Foo)).into_flat();
+    assert_eq!(v.variant_name(), "E2");
+    assert_eq!(v.name(), "foo");
+    assert_eq!(Enum2Flat::E1("bar").name(), "bar");
+    assert_eq!(Enum2Flat::E3(Foo).origin_path(), &["E3"]);
+}
@@ -0,0 +1,138 @@
+//! The generated items are documented, so that `ffi` is usable in crates denying `missing_docs`.
+#![deny(missing_docs)]
+
+use flat_enum::{flat, into_flat, FlatTarget};
+
+/// Input events.
+#[derive(FlatTarget, Clone, Copy, Debug, PartialEq)]
+pub enum Event1<A> {
+    /// Key and modifiers.
+    Key(A, keys::Modifiers),
+    /// Quit.
+    Quit,
+}
+
+/// Pointer and data events.
+#[derive(FlatTarget, Clone, Copy, Debug, PartialEq)]
+pub enum Event2 {
+    /// Move by the offset.
+    Move {
+        /// X offset.
+        x: f64,
+        /// Y offset.
+        y: f64,
+    },
+    /// Data at the pointer.
+    Data {
+        /// Header bytes.
+        bytes: [u8; 4],
+        /// Pointer to the data.
+        ptr: *const u16,
+    },
+}
+
+/// All events.
+#[into_flat(EventFlat)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// Input events.
+    #[flatten]
+    Event1(Event1<u32>),
+    /// Pointer and data events.
+    #[flatten]
+    Event2(Event2),
+    /// Timer tick.
+    Tick(i64),
+}
+
+/// Flat events.
+#[flat(Event, ffi)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventFlat {}
+
+/// Type aliases are resolved through `FfiSafe`.
+mod keys {
+    pub type Modifiers = u8;
+}
+
+#[test]
+fn test_ffi_roundtrip() {
+    let data = 5u16;
+    let values = [
+        EventFlat::Key(65, 1),
+        EventFlat::Quit,
+        EventFlat::Move { x: 1.5, y: -2.0 },
+        EventFlat::Data {
+            bytes: [1, 2, 3, 4],
+            ptr: &data,
+        },
+        EventFlat::Tick(-7),
+    ];
+    for (i, value) in values.into_iter().enumerate() {
+        let ffi = value.to_ffi();
+        assert_eq!(ffi.tag().map(|tag| tag as usize), Some(i));
+        assert_eq!(unsafe { EventFlat::from_ffi(&ffi) }, Some(value));
+    }
+    assert_eq!(EventFlatFfi::c_header().matches("typedef").count(), 7);
+}
+
+#[test]
+fn test_ffi_layout() {
+    use std::mem::{align_of, offset_of, size_of};
+    assert_eq!(size_of::<EventFlatFfiTag>(), 4);
+    assert_eq!(align_of::<EventFlatFfi>(), 8);
+    assert_eq!(offset_of!(EventFlatFfi_Move, y), 8);
+    assert_eq!(offset_of!(EventFlatFfi_Data, ptr), 8);
+    assert_eq!(size_of::<EventFlatFfiPayload>(), 16);
+    assert_eq!(size_of::<EventFlatFfi>(), 24);
+}
+
+#[test]
+fn test_ffi_header() {
+    let header = EventFlatFfi::c_header();
+    for decl in [
+        "#include <stdint.h>",
+        "    EventFlatFfiTag_Key = 0,",
+        "    EventFlatFfiTag_Tick = 4,",
+        "typedef struct EventFlatFfi_Key {\n    uint32_t _0;\n    uint8_t _1;\n} EventFlatFfi_Key;",
+        "    uint8_t bytes[4];\n    uint16_t const (*ptr);\n",
+        "    EventFlatFfi_Move Move;\n",
+        "typedef struct EventFlatFfi {\n    uint32_t tag;\n    EventFlatFfiPayload payload;\n} EventFlatFfi;",
+    ] {
+        assert!(header.contains(decl), "{} in {}", decl, header);
+    }
+    assert!(!header.contains("EventFlatFfi_Quit"));
+}
+
+/// Table with nested pointers.
+#[derive(FlatTarget, Clone, Copy, Debug, PartialEq)]
+pub enum Table {
+    /// Rows.
+    Rows(*const *mut u8, [*const i32; 2], *mut *const u8),
+}
+
+/// Table wrapper.
+#[into_flat(TableFlat)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TableOuter {
+    /// Table.
+    #[flatten]
+    Table(Table),
+}
+
+/// Flat table.
+#[flat(TableOuter, ffi)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TableFlat {}
+
+#[test]
+fn test_ffi_header_nested_pointer() {
+    let header = TableFlatFfi::c_header();
+    for decl in [
+        "    uint8_t (*const (*_0));\n",
+        "    int32_t const (*_1[2]);\n",
+        "    uint8_t const (*(*_2));\n",
+    ] {
+        assert!(header.contains(decl), "{} in {}", decl, header);
+    }
+}
@@ -0,0 +1,60 @@
+use flat_enum::{flat, into_flat, FlatTarget, IntoFlat, VariantInfo};
+
+#[allow(dead_code)]
+const _: () = {
+    #[derive(FlatTarget)]
+    #[flat_enum(local)]
+    pub enum Enum1<A> {
+        E1(A),
+        E2,
+    }
+
+    #[into_flat(Enum3Flat<A>, local)]
+    pub enum Enum3<A> {
+        #[flatten]
+        Enum1(Enum1<A>),
+        E3,
+    }
+
+    #[flat(Enum3<A>)]
+    #[derive(FlatTarget)]
+    #[flat_enum(local)]
+    pub enum Enum3Flat<A> {}
+
+    #[into_flat(Enum4Flat, local)]
+    pub enum Enum4 {
+        #[flatten]
+        Enum3(Enum3Flat<u8>),
+        E4(u8),
+    }
+
+    #[flat(Enum4)]
+    pub enum Enum4Flat {}
+
+    assert!(<Enum4Flat as VariantInfo>::VARIANT_COUNT == 4);
+};
+
+#[test]
+fn test_local() {
+    #[derive(FlatTarget)]
+    #[flat_enum(local)]
+    enum Enum1 {
+        E1(u8),
+    }
+
+    #[into_flat(Enum3Flat, local)]
+    enum Enum3 {
+        #[flatten]
+        Enum1(Enum1),
+        E2,
+    }
+
+    #[flat(Enum3)]
+    enum Enum3Flat {}
+
+    assert!(matches!(
+        Enum3::Enum1(Enum1::E1(1)).into_flat(),
+        Enum3Flat::E1(1)
+    ));
+    assert_eq!(Enum3Flat::E2.origin_path(), &["E2"]);
+}
@@ -0,0 +1,117 @@
+use flat_enum::{flat, into_flat, FlatTarget, IntoFlat};
+use std::fmt::Debug;
+
+#[derive(FlatTarget, Debug, Clone)]
+#[flat_enum(map(A))]
+pub enum Enum1<A> {
+    E1(A),
+    E2(Vec<A>),
+    E3 { x: Option<A>, name: String },
+    E4([A; 2]),
+}
+
+#[into_flat(Enum2Flat<'a, A>, map(A))]
+#[derive(Debug, Clone)]
+pub enum Enum2<'a, A> {
+    #[flatten]
+    Enum1(Enum1<A>),
+    E5(&'a str, A),
+    E6,
+}
+
+#[flat(Enum2<'a, A>, map(A))]
+#[derive(FlatTarget, Debug, Clone)]
+pub enum Enum2Flat<'a, A> {}
+
+#[into_flat(Enum3Flat<'a, T>, map(T))]
+#[derive(Debug, Clone)]
+pub enum Enum3<'a, T> {
+    #[flatten]
+    Enum2(Enum2Flat<'a, T>),
+    E7 {
+        v: Vec<T>,
+    },
+}
+
+#[flat(Enum3<'a, T>)]
+#[derive(FlatTarget, Debug)]
+#[flat_enum(map(T))]
+pub enum Enum3Flat<'a, T> {}
+
+fn values() -> Vec<Enum3<'static, u8>> {
+    vec![
+        Enum3::Enum2(Enum2::Enum1(Enum1::E1(1)).into_flat()),
+        Enum3::Enum2(Enum2::Enum1(Enum1::E2(vec![1, 2])).into_flat()),
+        Enum3::Enum2(
+            Enum2::Enum1(Enum1::E3 {
+                x: Some(3),
+                name: "abc".to_owned(),
+            })
+            .into_flat(),
+        ),
+        Enum3::Enum2(
+            Enum2::Enum1(Enum1::E3 {
+                x: None,
+                name: "def".to_owned(),
+            })
+            .into_flat(),
+        ),
+        Enum3::Enum2(Enum2::Enum1(Enum1::E4([4, 5])).into_flat()),
+        Enum3::Enum2(Enum2::E5("ghi", 6).into_flat()),
+        Enum3::Enum2(Enum2::E6.into_flat()),
+        Enum3::E7 { v: vec![7, 8, 9] },
+    ]
+}
+
+fn debug<T: Debug>(value: T) -> String {
+    format!("{:?}", value)
+}
+
+#[test]
+fn test_map() {
+    assert_eq!(
+        debug(Enum1::E2(vec![1, 2]).map(|a| a * 2)),
+        debug(Enum1::E2(vec![2, 4]))
+    );
+    assert_eq!(
+        debug(Enum2Flat::E5("abc", 1).map(|a| a.to_string())),
+        debug(Enum2Flat::E5("abc", "1".to_owned()))
+    );
+    let mut count = 0;
+    let _ = Enum2Flat::E4([1, 2]).map(|a| {
+        count += 1;
+        a
+    });
+    assert_eq!(count, 2);
+}
+
+#[test]
+fn test_map_commutes() {
+    let f = |a: u8| a as u32 * 10;
+    for value in values() {
+        assert_eq!(
+            debug(value.clone().map(f).into_flat()),
+            debug(value.clone().into_flat().map(f))
+        );
+        assert_eq!(
+            debug(value.to_ref().into_flat()),
+            debug(value.clone().into_flat().to_ref())
+        );
+    }
+}
+
+#[test]
+fn test_to_ref() {
+    let value: Enum3Flat<u8> = Enum3Flat::E3 {
+        x: Some(1),
+        name: "abc".to_owned(),
+    };
+    let Enum3Flat::E3 { x, name } = value.to_ref() else {
+        unreachable!()
+    };
+    let Enum3Flat::E3 { x: Some(orig), .. } = &value else {
+        unreachable!()
+    };
+    assert!(std::ptr::eq(x.unwrap(), orig));
+    assert_eq!(name, "abc");
+}
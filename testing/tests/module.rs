@@ -0,0 +1,53 @@
+use flat_enum::{flat, IntoFlat};
+
+mod inner {
+    use flat_enum::{into_flat, FlatTarget};
+
+    /// Not in scope where the flat enum is defined.
+    #[derive(FlatTarget, Debug, PartialEq)]
+    pub enum Enum1<A> {
+        E1(A),
+        E2 { s: Label },
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Label(pub &'static str);
+
+    #[into_flat(super::Enum2Flat<A>)]
+    #[derive(Debug, PartialEq)]
+    pub enum Enum2<A> {
+        #[flatten]
+        Enum1(Enum1<A>),
+        E3(Label, A),
+    }
+}
+
+/// Only the path of the structured enum is needed here, also with the options other than
+/// `structured(..)` and `visitor`.
+#[flat(inner::Enum2<A>, accessors, map(A))]
+#[derive(Debug, PartialEq)]
+pub enum Enum2Flat<A> {}
+
+#[test]
+fn test_module() {
+    use inner::{Enum1, Enum2, Label};
+    let values = vec![
+        Enum2::Enum1(Enum1::E1(1u8)),
+        Enum2::Enum1(Enum1::E2 { s: Label("a") }),
+        Enum2::E3(Label("b"), 2),
+    ];
+    let flat: Vec<_> = values.into_iter().map(IntoFlat::into_flat).collect();
+    assert_eq!(flat[0], Enum2Flat::E1(1));
+    assert_eq!(flat[1], Enum2Flat::E2 { s: Label("a") });
+    assert_eq!(flat[2], Enum2Flat::E3(Label("b"), 2));
+    let back: Vec<_> = flat.into_iter().map(Enum2::from_flat).collect();
+    assert_eq!(back[2], Enum2::E3(Label("b"), 2));
+}
+
+#[test]
+fn test_module_options() {
+    use inner::Label;
+    let v = Enum2Flat::E3(Label("a"), 1u8).map(|a| a + 1);
+    assert_eq!(v.as_e3(), Some((&Label("a"), &2)));
+    assert_eq!(v.to_ref().into_e3().ok(), Some((Label("a"), &2)));
+}
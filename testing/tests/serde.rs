@@ -0,0 +1,160 @@
+use flat_enum::{flat, into_flat, FlatTarget, IntoFlat};
+use serde::{Deserialize, Serialize};
+
+#[derive(FlatTarget, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Enum1<A> {
+    E1(A),
+    E2(),
+    E3(String, A),
+}
+
+#[derive(FlatTarget, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Enum2 {
+    E4 { x: u8 },
+}
+
+#[into_flat(Enum3Flat<A>)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Enum3<A> {
+    E5,
+    #[flatten]
+    Enum1(Enum1<A>),
+    E6(A),
+    #[flatten]
+    Enum2(Enum2),
+}
+
+#[flat(Enum3<A>, serde)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Enum3Flat<A> {}
+
+#[into_flat(Enum4Flat<A>)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Enum4<A> {
+    E5,
+    #[flatten]
+    Enum1(Enum1<A>),
+    E6(A),
+    #[flatten]
+    Enum2(Enum2),
+}
+
+#[flat(Enum4<A>, serde(repr = "structured"))]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Enum4Flat<A> {}
+
+#[test]
+fn test_serde_flat() {
+    let values = [
+        (Enum3Flat::E5, r#""E5""#),
+        (Enum3Flat::E1(1u8), r#"{"E1":1}"#),
+        (Enum3Flat::E2(), r#"{"E2":[]}"#),
+        (Enum3Flat::E3("a".to_owned(), 2), r#"{"E3":["a",2]}"#),
+        (Enum3Flat::E6(3), r#"{"E6":3}"#),
+        (Enum3Flat::E4 { x: 4 }, r#"{"E4":{"x":4}}"#),
+    ];
+    for (value, json) in values {
+        assert_eq!(serde_json::to_string(&value).unwrap(), json);
+        assert_eq!(serde_json::from_str::<Enum3Flat<u8>>(json).unwrap(), value);
+    }
+    assert!(serde_json::from_str::<Enum3Flat<u8>>(r#"{"Enum1":{"E1":1}}"#).is_err());
+}
+
+#[test]
+fn test_serde_structured() {
+    let values = [
+        Enum4::E5,
+        Enum4::Enum1(Enum1::E1(1u8)),
+        Enum4::Enum1(Enum1::E2()),
+        Enum4::Enum1(Enum1::E3("a".to_owned(), 2)),
+        Enum4::E6(3),
+        Enum4::Enum2(Enum2::E4 { x: 4 }),
+    ];
+    for value in values {
+        let json = serde_json::to_string(&value).unwrap();
+        let flat = value.clone().into_flat();
+        assert_eq!(serde_json::to_string(&flat).unwrap(), json);
+        assert_eq!(serde_json::from_str::<Enum4Flat<u8>>(&json).unwrap(), flat);
+    }
+    assert_eq!(
+        serde_json::to_string(&Enum4Flat::E1(1u8)).unwrap(),
+        r#"{"Enum1":{"E1":1}}"#
+    );
+}
+
+/// The same shapes as `Enum3Flat` and `Enum5Flat` in `test.rs`, where the flat enum is flattened
+/// again and the enums have lifetimes and const generics.
+mod nested {
+    use flat_enum::{flat, into_flat, FlatTarget};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(FlatTarget, Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub enum Enum2<B> {
+        E4(B),
+        E5(),
+    }
+
+    #[into_flat(Enum3Flat<A, B>)]
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub enum Enum3<A, B> {
+        #[flatten]
+        MyEnum1(super::Enum1<A>),
+        #[flatten]
+        MyEnum2(Enum2<B>),
+        E6,
+    }
+
+    #[flat(Enum3<A, B>, serde(repr = "structured"))]
+    #[derive(FlatTarget, Clone, Debug, PartialEq)]
+    pub enum Enum3Flat<A, B> {}
+
+    #[derive(FlatTarget, Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub enum Enum4<'a, const N: usize, A> {
+        E7(&'a str, A),
+        E8 { y: [u8; 2] },
+    }
+
+    #[into_flat(Enum5Flat<'a, A>)]
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub enum Enum5<'a, A> {
+        #[flatten]
+        MyEnum3(Enum3Flat<A, A>),
+        #[flatten]
+        #[serde(borrow)]
+        MyEnum4(Enum4<'a, 3, A>),
+    }
+
+    #[flat(Enum5<'a, A>, serde(repr = "structured"))]
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Enum5Flat<'a, A> {}
+}
+
+#[test]
+fn test_serde_structured_nested() {
+    use nested::*;
+    let values = [
+        (Enum5Flat::E1(1u8), r#"{"MyEnum3":{"MyEnum1":{"E1":1}}}"#),
+        (Enum5Flat::E2(), r#"{"MyEnum3":{"MyEnum1":{"E2":[]}}}"#),
+        (
+            Enum5Flat::E3("a".to_owned(), 2),
+            r#"{"MyEnum3":{"MyEnum1":{"E3":["a",2]}}}"#,
+        ),
+        (Enum5Flat::E4(3), r#"{"MyEnum3":{"MyEnum2":{"E4":3}}}"#),
+        (Enum5Flat::E5(), r#"{"MyEnum3":{"MyEnum2":{"E5":[]}}}"#),
+        (Enum5Flat::E6, r#"{"MyEnum3":"E6"}"#),
+        (Enum5Flat::E7("b", 4), r#"{"MyEnum4":{"E7":["b",4]}}"#),
+        (
+            Enum5Flat::E8 { y: [5, 6] },
+            r#"{"MyEnum4":{"E8":{"y":[5,6]}}}"#,
+        ),
+    ];
+    for (flat, json) in values {
+        let structured = Enum5::from_flat(flat.clone());
+        assert_eq!(serde_json::to_string(&structured).unwrap(), json);
+        assert_eq!(serde_json::to_string(&flat).unwrap(), json);
+        assert_eq!(
+            serde_json::from_str::<Enum5Flat<'_, u8>>(json).unwrap(),
+            flat
+        );
+    }
+}
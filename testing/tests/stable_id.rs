@@ -0,0 +1,100 @@
+use flat_enum::{flat, into_flat, FlatTarget, VariantInfo};
+
+#[derive(FlatTarget)]
+pub enum Enum1<A> {
+    #[flat_enum(id = 3)]
+    E1(A),
+    #[flat_enum(id = 1)]
+    E2(),
+    #[flat_enum(id = 2)]
+    E3 { s: String },
+}
+
+#[derive(FlatTarget)]
+pub enum Enum2 {
+    #[flat_enum(id = 1)]
+    E4,
+}
+
+#[into_flat(Enum3Flat<A>)]
+pub enum Enum3<A> {
+    /// The unit variant with the stable ID.
+    #[flat_enum(id = 7)]
+    E5,
+    #[flatten(id_base = 1000)]
+    Enum1(Enum1<A>),
+    #[flat_enum(id = 8)]
+    E6(A),
+    #[flatten(id_base = 2000)]
+    Enum2(Enum2),
+}
+
+#[flat(Enum3<A>)]
+#[derive(FlatTarget)]
+pub enum Enum3Flat<A> {}
+
+#[into_flat(Enum4Flat)]
+pub enum Enum4 {
+    #[flatten(id_base = 10000)]
+    Enum3(Enum3Flat<u8>),
+    #[flat_enum(id = 1)]
+    E7,
+}
+
+#[flat(Enum4)]
+pub enum Enum4Flat {}
+
+#[test]
+fn test_stable_id() {
+    let values: [(Enum3Flat<u8>, u32); 6] = [
+        (Enum3Flat::E5, 7),
+        (Enum3Flat::E1(1), 1003),
+        (Enum3Flat::E2(), 1001),
+        (Enum3Flat::E3 { s: "a".to_owned() }, 1002),
+        (Enum3Flat::E6(2), 8),
+        (Enum3Flat::E4, 2001),
+    ];
+    for (value, id) in values {
+        assert_eq!(value.stable_id(), id);
+        assert_eq!(
+            Enum3Flat::<u8>::from_stable_id(id),
+            Some(value.variant_index())
+        );
+    }
+    assert_eq!(Enum3Flat::<u8>::from_stable_id(1000), None);
+}
+
+#[test]
+fn test_stable_id_nested() {
+    assert_eq!(Enum4Flat::E1(1).stable_id(), 11003);
+    assert_eq!(Enum4Flat::E4.stable_id(), 12001);
+    assert_eq!(Enum4Flat::E7.stable_id(), 1);
+    assert_eq!(
+        Enum4Flat::from_stable_id(11002),
+        Some(Enum4Flat::E3 { s: String::new() }.variant_index())
+    );
+}
+
+#[test]
+fn test_describe() {
+    let desc = flat_enum::describe::<Enum4Flat>();
+    assert_eq!(desc.name, "Enum4Flat");
+    assert_eq!(desc.variants.len(), Enum4Flat::VARIANT_COUNT);
+    let e3 = &desc.variants[3];
+    assert_eq!(e3.name, "E3");
+    assert_eq!(e3.groups, &["Enum3", "Enum1"]);
+    assert_eq!(e3.defined_in, "stable_id::Enum1");
+    assert_eq!(e3.fields[0].name, Some("s"));
+    assert_eq!(e3.id, Some(11002));
+    for (i, variant) in desc.variants.iter().enumerate() {
+        assert_eq!(Enum4Flat::from_stable_id(variant.id.unwrap()), Some(i));
+    }
+
+    let json = serde_json::to_value(&desc).unwrap();
+    assert_eq!(
+        json["variants"][3]["groups"],
+        serde_json::json!(["Enum3", "Enum1"])
+    );
+    assert_eq!(json["variants"][3]["fields"][0]["ty"], "String");
+    assert_eq!(json["variants"][6]["id"], 1);
+}
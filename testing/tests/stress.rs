@@ -0,0 +1,290 @@
+//! The enums with 50 groups and 651 variants, and with 140 groups, which expand without
+//! `recursion_limit`.
+
+use flat_enum::{flat, into_flat, FlatTarget, IntoFlat, VariantInfo};
+
+macro_rules! define_stress {
+    ($($group:ident $normal:ident [$($variant:ident)*])*) => {
+        $(
+            #[derive(FlatTarget, Clone, Debug, PartialEq)]
+            pub enum $group {
+                $($variant(u16),)*
+            }
+        )*
+
+        #[into_flat(StressFlat)]
+        #[derive(Clone, Debug, PartialEq)]
+        pub enum Stress {
+            First { x: u8 },
+            $(
+                #[flatten]
+                $group($group),
+                $normal,
+            )*
+        }
+
+        #[flat(Stress)]
+        #[derive(Debug, PartialEq)]
+        pub enum StressFlat {}
+    };
+}
+
+define_stress! {
+    Group00 N00 [A00 B00 C00 D00 E00 F00 G00 H00 I00 J00 K00 L00]
+    Group01 N01 [A01 B01 C01 D01 E01 F01 G01 H01 I01 J01 K01 L01]
+    Group02 N02 [A02 B02 C02 D02 E02 F02 G02 H02 I02 J02 K02 L02]
+    Group03 N03 [A03 B03 C03 D03 E03 F03 G03 H03 I03 J03 K03 L03]
+    Group04 N04 [A04 B04 C04 D04 E04 F04 G04 H04 I04 J04 K04 L04]
+    Group05 N05 [A05 B05 C05 D05 E05 F05 G05 H05 I05 J05 K05 L05]
+    Group06 N06 [A06 B06 C06 D06 E06 F06 G06 H06 I06 J06 K06 L06]
+    Group07 N07 [A07 B07 C07 D07 E07 F07 G07 H07 I07 J07 K07 L07]
+    Group08 N08 [A08 B08 C08 D08 E08 F08 G08 H08 I08 J08 K08 L08]
+    Group09 N09 [A09 B09 C09 D09 E09 F09 G09 H09 I09 J09 K09 L09]
+    Group10 N10 [A10 B10 C10 D10 E10 F10 G10 H10 I10 J10 K10 L10]
+    Group11 N11 [A11 B11 C11 D11 E11 F11 G11 H11 I11 J11 K11 L11]
+    Group12 N12 [A12 B12 C12 D12 E12 F12 G12 H12 I12 J12 K12 L12]
+    Group13 N13 [A13 B13 C13 D13 E13 F13 G13 H13 I13 J13 K13 L13]
+    Group14 N14 [A14 B14 C14 D14 E14 F14 G14 H14 I14 J14 K14 L14]
+    Group15 N15 [A15 B15 C15 D15 E15 F15 G15 H15 I15 J15 K15 L15]
+    Group16 N16 [A16 B16 C16 D16 E16 F16 G16 H16 I16 J16 K16 L16]
+    Group17 N17 [A17 B17 C17 D17 E17 F17 G17 H17 I17 J17 K17 L17]
+    Group18 N18 [A18 B18 C18 D18 E18 F18 G18 H18 I18 J18 K18 L18]
+    Group19 N19 [A19 B19 C19 D19 E19 F19 G19 H19 I19 J19 K19 L19]
+    Group20 N20 [A20 B20 C20 D20 E20 F20 G20 H20 I20 J20 K20 L20]
+    Group21 N21 [A21 B21 C21 D21 E21 F21 G21 H21 I21 J21 K21 L21]
+    Group22 N22 [A22 B22 C22 D22 E22 F22 G22 H22 I22 J22 K22 L22]
+    Group23 N23 [A23 B23 C23 D23 E23 F23 G23 H23 I23 J23 K23 L23]
+    Group24 N24 [A24 B24 C24 D24 E24 F24 G24 H24 I24 J24 K24 L24]
+    Group25 N25 [A25 B25 C25 D25 E25 F25 G25 H25 I25 J25 K25 L25]
+    Group26 N26 [A26 B26 C26 D26 E26 F26 G26 H26 I26 J26 K26 L26]
+    Group27 N27 [A27 B27 C27 D27 E27 F27 G27 H27 I27 J27 K27 L27]
+    Group28 N28 [A28 B28 C28 D28 E28 F28 G28 H28 I28 J28 K28 L28]
+    Group29 N29 [A29 B29 C29 D29 E29 F29 G29 H29 I29 J29 K29 L29]
+    Group30 N30 [A30 B30 C30 D30 E30 F30 G30 H30 I30 J30 K30 L30]
+    Group31 N31 [A31 B31 C31 D31 E31 F31 G31 H31 I31 J31 K31 L31]
+    Group32 N32 [A32 B32 C32 D32 E32 F32 G32 H32 I32 J32 K32 L32]
+    Group33 N33 [A33 B33 C33 D33 E33 F33 G33 H33 I33 J33 K33 L33]
+    Group34 N34 [A34 B34 C34 D34 E34 F34 G34 H34 I34 J34 K34 L34]
+    Group35 N35 [A35 B35 C35 D35 E35 F35 G35 H35 I35 J35 K35 L35]
+    Group36 N36 [A36 B36 C36 D36 E36 F36 G36 H36 I36 J36 K36 L36]
+    Group37 N37 [A37 B37 C37 D37 E37 F37 G37 H37 I37 J37 K37 L37]
+    Group38 N38 [A38 B38 C38 D38 E38 F38 G38 H38 I38 J38 K38 L38]
+    Group39 N39 [A39 B39 C39 D39 E39 F39 G39 H39 I39 J39 K39 L39]
+    Group40 N40 [A40 B40 C40 D40 E40 F40 G40 H40 I40 J40 K40 L40]
+    Group41 N41 [A41 B41 C41 D41 E41 F41 G41 H41 I41 J41 K41 L41]
+    Group42 N42 [A42 B42 C42 D42 E42 F42 G42 H42 I42 J42 K42 L42]
+    Group43 N43 [A43 B43 C43 D43 E43 F43 G43 H43 I43 J43 K43 L43]
+    Group44 N44 [A44 B44 C44 D44 E44 F44 G44 H44 I44 J44 K44 L44]
+    Group45 N45 [A45 B45 C45 D45 E45 F45 G45 H45 I45 J45 K45 L45]
+    Group46 N46 [A46 B46 C46 D46 E46 F46 G46 H46 I46 J46 K46 L46]
+    Group47 N47 [A47 B47 C47 D47 E47 F47 G47 H47 I47 J47 K47 L47]
+    Group48 N48 [A48 B48 C48 D48 E48 F48 G48 H48 I48 J48 K48 L48]
+    Group49 N49 [A49 B49 C49 D49 E49 F49 G49 H49 I49 J49 K49 L49]
+}
+
+/// More `#[flatten]` variants than the default `recursion_limit` of 128.
+macro_rules! define_wide {
+    ($($group:ident [$($variant:ident)*])*) => {
+        $(
+            #[derive(FlatTarget, Clone, Debug, PartialEq)]
+            pub enum $group {
+                $($variant,)*
+            }
+        )*
+
+        #[into_flat(WideFlat)]
+        #[derive(Clone, Debug, PartialEq)]
+        pub enum Wide {
+            $(
+                #[flatten]
+                $group($group),
+            )*
+        }
+
+        #[flat(Wide)]
+        #[derive(Debug, PartialEq)]
+        pub enum WideFlat {}
+    };
+}
+
+define_wide! {
+    Wide000 [P000 Q000]
+    Wide001 [P001 Q001]
+    Wide002 [P002 Q002]
+    Wide003 [P003 Q003]
+    Wide004 [P004 Q004]
+    Wide005 [P005 Q005]
+    Wide006 [P006 Q006]
+    Wide007 [P007 Q007]
+    Wide008 [P008 Q008]
+    Wide009 [P009 Q009]
+    Wide010 [P010 Q010]
+    Wide011 [P011 Q011]
+    Wide012 [P012 Q012]
+    Wide013 [P013 Q013]
+    Wide014 [P014 Q014]
+    Wide015 [P015 Q015]
+    Wide016 [P016 Q016]
+    Wide017 [P017 Q017]
+    Wide018 [P018 Q018]
+    Wide019 [P019 Q019]
+    Wide020 [P020 Q020]
+    Wide021 [P021 Q021]
+    Wide022 [P022 Q022]
+    Wide023 [P023 Q023]
+    Wide024 [P024 Q024]
+    Wide025 [P025 Q025]
+    Wide026 [P026 Q026]
+    Wide027 [P027 Q027]
+    Wide028 [P028 Q028]
+    Wide029 [P029 Q029]
+    Wide030 [P030 Q030]
+    Wide031 [P031 Q031]
+    Wide032 [P032 Q032]
+    Wide033 [P033 Q033]
+    Wide034 [P034 Q034]
+    Wide035 [P035 Q035]
+    Wide036 [P036 Q036]
+    Wide037 [P037 Q037]
+    Wide038 [P038 Q038]
+    Wide039 [P039 Q039]
+    Wide040 [P040 Q040]
+    Wide041 [P041 Q041]
+    Wide042 [P042 Q042]
+    Wide043 [P043 Q043]
+    Wide044 [P044 Q044]
+    Wide045 [P045 Q045]
+    Wide046 [P046 Q046]
+    Wide047 [P047 Q047]
+    Wide048 [P048 Q048]
+    Wide049 [P049 Q049]
+    Wide050 [P050 Q050]
+    Wide051 [P051 Q051]
+    Wide052 [P052 Q052]
+    Wide053 [P053 Q053]
+    Wide054 [P054 Q054]
+    Wide055 [P055 Q055]
+    Wide056 [P056 Q056]
+    Wide057 [P057 Q057]
+    Wide058 [P058 Q058]
+    Wide059 [P059 Q059]
+    Wide060 [P060 Q060]
+    Wide061 [P061 Q061]
+    Wide062 [P062 Q062]
+    Wide063 [P063 Q063]
+    Wide064 [P064 Q064]
+    Wide065 [P065 Q065]
+    Wide066 [P066 Q066]
+    Wide067 [P067 Q067]
+    Wide068 [P068 Q068]
+    Wide069 [P069 Q069]
+    Wide070 [P070 Q070]
+    Wide071 [P071 Q071]
+    Wide072 [P072 Q072]
+    Wide073 [P073 Q073]
+    Wide074 [P074 Q074]
+    Wide075 [P075 Q075]
+    Wide076 [P076 Q076]
+    Wide077 [P077 Q077]
+    Wide078 [P078 Q078]
+    Wide079 [P079 Q079]
+    Wide080 [P080 Q080]
+    Wide081 [P081 Q081]
+    Wide082 [P082 Q082]
+    Wide083 [P083 Q083]
+    Wide084 [P084 Q084]
+    Wide085 [P085 Q085]
+    Wide086 [P086 Q086]
+    Wide087 [P087 Q087]
+    Wide088 [P088 Q088]
+    Wide089 [P089 Q089]
+    Wide090 [P090 Q090]
+    Wide091 [P091 Q091]
+    Wide092 [P092 Q092]
+    Wide093 [P093 Q093]
+    Wide094 [P094 Q094]
+    Wide095 [P095 Q095]
+    Wide096 [P096 Q096]
+    Wide097 [P097 Q097]
+    Wide098 [P098 Q098]
+    Wide099 [P099 Q099]
+    Wide100 [P100 Q100]
+    Wide101 [P101 Q101]
+    Wide102 [P102 Q102]
+    Wide103 [P103 Q103]
+    Wide104 [P104 Q104]
+    Wide105 [P105 Q105]
+    Wide106 [P106 Q106]
+    Wide107 [P107 Q107]
+    Wide108 [P108 Q108]
+    Wide109 [P109 Q109]
+    Wide110 [P110 Q110]
+    Wide111 [P111 Q111]
+    Wide112 [P112 Q112]
+    Wide113 [P113 Q113]
+    Wide114 [P114 Q114]
+    Wide115 [P115 Q115]
+    Wide116 [P116 Q116]
+    Wide117 [P117 Q117]
+    Wide118 [P118 Q118]
+    Wide119 [P119 Q119]
+    Wide120 [P120 Q120]
+    Wide121 [P121 Q121]
+    Wide122 [P122 Q122]
+    Wide123 [P123 Q123]
+    Wide124 [P124 Q124]
+    Wide125 [P125 Q125]
+    Wide126 [P126 Q126]
+    Wide127 [P127 Q127]
+    Wide128 [P128 Q128]
+    Wide129 [P129 Q129]
+    Wide130 [P130 Q130]
+    Wide131 [P131 Q131]
+    Wide132 [P132 Q132]
+    Wide133 [P133 Q133]
+    Wide134 [P134 Q134]
+    Wide135 [P135 Q135]
+    Wide136 [P136 Q136]
+    Wide137 [P137 Q137]
+    Wide138 [P138 Q138]
+    Wide139 [P139 Q139]
+}
+
+#[test]
+fn test_stress() {
+    assert_eq!(StressFlat::VARIANT_COUNT, 651);
+    let values = [
+        (Stress::First { x: 1 }, 0),
+        (Stress::Group00(Group00::A00(2)), 1),
+        (Stress::N00, 13),
+        (Stress::Group25(Group25::F25(3)), 331),
+        (Stress::Group49(Group49::L49(4)), 649),
+        (Stress::N49, 650),
+    ];
+    for (value, index) in values {
+        let flat = value.clone().into_flat();
+        assert_eq!(flat.variant_index(), index);
+        assert_eq!(Stress::from_flat(flat), value);
+    }
+    assert_eq!(
+        Stress::from_flat(StressFlat::K30(5)),
+        Stress::Group30(Group30::K30(5))
+    );
+    assert_eq!(StressFlat::F25(3).origin_path(), &["Group25", "F25"]);
+}
+
+#[test]
+fn test_wide() {
+    assert_eq!(WideFlat::VARIANT_COUNT, 280);
+    let values = [
+        (Wide::Wide000(Wide000::P000), 0),
+        (Wide::Wide070(Wide070::Q070), 141),
+        (Wide::Wide139(Wide139::Q139), 279),
+    ];
+    for (value, index) in values {
+        let flat = value.clone().into_flat();
+        assert_eq!(flat.variant_index(), index);
+        assert_eq!(Wide::from_flat(flat), value);
+    }
+    assert_eq!(WideFlat::P128.origin_path(), &["Wide128", "P128"]);
+}
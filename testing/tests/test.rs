@@ -31,6 +31,37 @@ pub enum Enum3Flat<A, B> {}
 #[test]
 fn test_enum3() {}
 
+#[derive(FlatTarget, Debug, PartialEq)]
+pub enum Named {
+    E9 { a: u8, b: String },
+}
+
+#[into_flat(NamedOuterFlat)]
+#[derive(Debug, PartialEq)]
+pub enum NamedOuter {
+    #[flatten]
+    MyNamed(Named),
+}
+
+#[flat(NamedOuter)]
+pub enum NamedOuterFlat {}
+
+#[test]
+fn test_named_fields() {
+    use flat_enum::IntoFlat;
+    let v = NamedOuterFlat::E9 {
+        a: 1,
+        b: "b".to_owned(),
+    };
+    assert_eq!(
+        NamedOuter::from_flat(v),
+        NamedOuter::MyNamed(Named::E9 {
+            a: 1,
+            b: "b".to_owned()
+        })
+    );
+}
+
 mod m1 {
     use flat_enum::FlatTarget;
     #[derive(FlatTarget)]
@@ -54,3 +85,219 @@ pub mod m2 {
     #[flat(Enum5<'a, A>)]
     pub enum Enum5Flat<'a, A> {}
 }
+
+#[test]
+fn test_variant_info() {
+    use flat_enum::VariantInfo;
+    let v: m2::Enum5Flat<'_, u8> = m2::Enum5Flat::E1(1);
+    assert_eq!(v.variant_name(), "E1");
+    assert_eq!(v.variant_index(), 0);
+    assert_eq!(v.origin_path(), &["MyEnum3", "MyEnum1", "E1"]);
+    let v: m2::Enum5Flat<'_, u8> = m2::Enum5Flat::E6;
+    assert_eq!(v.origin_path(), &["MyEnum3", "E6"]);
+    let v: m2::Enum5Flat<'_, u8> = m2::Enum5Flat::E8(&[1, 2, 3, 4]);
+    assert_eq!(v.variant_index(), 7);
+    assert_eq!(v.origin_path(), &["MyEnum6", "E8"]);
+    assert_eq!(m2::Enum5Flat::<'_, u8>::VARIANT_COUNT, 8);
+    assert_eq!(
+        m2::Enum5::<'_, u8>::VARIANT_NAMES,
+        &["MyEnum3", "MyEnum4", "MyEnum6"]
+    );
+    assert_eq!(Enum1::<u8>::E3(String::new()).variant_index(), 2);
+    assert_eq!(Enum1::<u8>::E2().origin_path(), &["E2"]);
+}
+
+#[test]
+fn test_descriptor() {
+    use flat_enum::{Describe, EnumKind, FieldsKind};
+    let desc = <m2::Enum5Flat<'_, u8> as Describe>::DESCRIPTOR;
+    assert_eq!(desc.name, "Enum5Flat");
+    assert_eq!(desc.path, "test::m2::Enum5Flat");
+    assert_eq!(desc.kind, EnumKind::Flat);
+    assert_eq!(
+        desc.flat_variants()
+            .iter()
+            .map(|variant| variant.name)
+            .collect::<Vec<_>>(),
+        &["E1", "E2", "E3", "E4", "E5", "E6", "E7", "E8"]
+    );
+
+    let group = &desc.variants[1];
+    assert_eq!(group.name, "MyEnum4");
+    assert_eq!(group.fields[0].ty, "super::m1::Enum4<'a, 3, A>");
+    let inner = group.flatten.unwrap();
+    assert_eq!(inner.path, "test::m1::Enum4");
+    assert_eq!(inner.kind, EnumKind::FlatTarget);
+    assert_eq!(inner.variants[0].kind, FieldsKind::Unnamed);
+    assert_eq!(
+        inner.variants[0]
+            .fields
+            .iter()
+            .map(|field| field.ty)
+            .collect::<Vec<_>>(),
+        &["&'a str", "[A; N]"]
+    );
+
+    let structured = <m2::Enum5<'_, u8> as Describe>::DESCRIPTOR;
+    assert_eq!(structured.kind, EnumKind::Structured);
+    assert_eq!(structured.variants, desc.variants);
+    assert_eq!(desc.variants[0].flatten.unwrap().kind, EnumKind::Flat);
+}
+
+mod m3 {
+    use flat_enum::{flat, into_flat, FlatTarget};
+
+    /// `Enum1` whose `E3` has the different field type.
+    #[derive(FlatTarget)]
+    pub enum Enum1<A> {
+        E1(A),
+        E2(),
+        E3(Vec<u8>),
+    }
+
+    #[allow(unused)]
+    #[into_flat(Enum3Flat<A, B>)]
+    pub enum Enum3<A, B> {
+        #[flatten]
+        MyEnum1(Enum1<A>),
+        #[flatten]
+        MyEnum2(super::Enum2<B>),
+        E6,
+    }
+
+    #[flat(Enum3<A, B>)]
+    pub enum Enum3Flat<A, B> {}
+}
+
+#[test]
+fn test_schema_hash() {
+    use flat_enum::Describe;
+    const HASH: u64 = <Enum3Flat<u8, u8> as Describe>::SCHEMA_HASH;
+    assert_eq!(HASH, <Enum3<u8, u8> as Describe>::SCHEMA_HASH);
+    assert_eq!(
+        HASH,
+        <Enum3Flat<u8, u8> as Describe>::DESCRIPTOR.schema_hash()
+    );
+    assert_eq!(
+        <Enum3Flat<u8, u8> as Describe>::SCHEMA_HASH,
+        <Enum3Flat<u16, ()> as Describe>::SCHEMA_HASH
+    );
+    assert_eq!(
+        <m3::Enum3Flat<u8, u8> as Describe>::SCHEMA_HASH,
+        <m3::Enum3<u8, u8> as Describe>::SCHEMA_HASH
+    );
+    // The change in the flattened enum is detected.
+    assert_ne!(
+        <Enum1<u8> as Describe>::SCHEMA_HASH,
+        <m3::Enum1<u8> as Describe>::SCHEMA_HASH
+    );
+    assert_ne!(HASH, <m3::Enum3Flat<u8, u8> as Describe>::SCHEMA_HASH);
+    assert_ne!(
+        <Enum1<u8> as Describe>::SCHEMA_HASH,
+        <Enum2<u8> as Describe>::SCHEMA_HASH
+    );
+}
+
+/// The identical enums in the different modules, whose helper macros are named after the
+/// locations.
+mod m4 {
+    use flat_enum::{flat, into_flat, FlatTarget};
+
+    #[derive(FlatTarget)]
+    pub enum Enum1 {
+        E1(u8),
+    }
+
+    #[into_flat(Enum3Flat)]
+    pub enum Enum3 {
+        #[flatten]
+        MyEnum1(Enum1),
+        E2,
+    }
+
+    #[flat(Enum3)]
+    pub enum Enum3Flat {}
+}
+
+mod m5 {
+    use flat_enum::{flat, into_flat, FlatTarget};
+
+    #[derive(FlatTarget)]
+    pub enum Enum1 {
+        E1(u8),
+    }
+
+    #[into_flat(Enum3Flat)]
+    pub enum Enum3 {
+        #[flatten]
+        MyEnum1(Enum1),
+        E2,
+    }
+
+    #[flat(Enum3)]
+    pub enum Enum3Flat {}
+}
+
+#[test]
+fn test_same_name() {
+    use flat_enum::VariantInfo;
+    assert_eq!(m4::Enum3Flat::E1(1).origin_path(), &["MyEnum1", "E1"]);
+    assert_eq!(m5::Enum3Flat::E2.variant_index(), 1);
+}
+
+/// The helper macros of the non-`pub` enums are not exported from the crate.
+mod m6 {
+    use flat_enum::{flat, into_flat, FlatTarget};
+
+    #[derive(FlatTarget)]
+    enum Private {
+        E1(u8),
+    }
+
+    #[derive(FlatTarget)]
+    pub(super) enum Restricted {
+        E2,
+        E3(u8),
+    }
+
+    #[into_flat(Enum7Flat)]
+    enum Enum7 {
+        #[flatten]
+        Private(Private),
+        E4,
+    }
+
+    #[flat(Enum7)]
+    enum Enum7Flat {}
+
+    #[test]
+    fn test_private() {
+        use flat_enum::IntoFlat;
+        assert!(matches!(
+            Enum7::Private(Private::E1(1)).into_flat(),
+            Enum7Flat::E1(1)
+        ));
+    }
+}
+
+#[into_flat(Enum8Flat)]
+enum Enum8 {
+    #[flatten]
+    Restricted(m6::Restricted),
+}
+
+#[flat(Enum8)]
+enum Enum8Flat {}
+
+#[test]
+fn test_restricted() {
+    use flat_enum::IntoFlat;
+    assert!(matches!(
+        Enum8::Restricted(m6::Restricted::E3(1)).into_flat(),
+        Enum8Flat::E3(1)
+    ));
+    assert!(matches!(
+        Enum8::from_flat(Enum8Flat::E2),
+        Enum8::Restricted(m6::Restricted::E2)
+    ));
+}
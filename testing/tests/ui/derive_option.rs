@@ -0,0 +1,10 @@
+use flat_enum::FlatTarget;
+
+/// A misspelled option is not taken as the path of the crate.
+#[derive(FlatTarget)]
+#[flat_enum(visiter)]
+pub enum Enum1 {
+    E1(u8),
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+use flat_enum::{flat, into_flat, FlatTarget};
+
+#[derive(FlatTarget)]
+pub enum Enum1 {
+    E1(u8),
+}
+
+#[into_flat(Enum2Flat)]
+pub enum Enum2 {
+    #[flatten]
+    Enum1(Enum1),
+}
+
+/// The error is reported at `common`, which needs the fields.
+#[flat(Enum2, common)]
+pub enum Enum2Flat {}
+
+fn main() {}
@@ -0,0 +1,17 @@
+use flat_enum::{flat, into_flat};
+
+pub enum Enum1 {
+    E1(u8),
+}
+
+#[into_flat(Enum2Flat)]
+pub enum Enum2 {
+    #[flatten]
+    Enum1(Enum1),
+    E2,
+}
+
+#[flat(Enum2)]
+pub enum Enum2Flat {}
+
+fn main() {}
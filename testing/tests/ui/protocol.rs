@@ -0,0 +1,11 @@
+use flat_enum::FlatTarget;
+
+#[derive(FlatTarget)]
+pub enum Enum1 {
+    E1(u8),
+}
+
+// The helper macro called by the other version of `flat_enum`.
+Enum1!(@emit_enum flat_enum = "0.0.0", protocol = 0, args = {}, enum_decl = {},);
+
+fn main() {}
@@ -0,0 +1,21 @@
+use flat_enum::{flat, into_flat, FlatTarget};
+
+// The derived `Ord` puts `B` before `A`.
+#[derive(FlatTarget, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Enum1 {
+    A = 2,
+    B = 1,
+}
+
+#[into_flat(Enum2Flat)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Enum2 {
+    #[flatten]
+    Enum1(Enum1),
+    C,
+}
+
+#[flat(Enum2, structured(PartialEq, Eq, Ord, Hash))]
+pub enum Enum2Flat {}
+
+fn main() {}
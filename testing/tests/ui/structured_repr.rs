@@ -0,0 +1,22 @@
+use flat_enum::{flat, into_flat, FlatTarget};
+
+#[derive(FlatTarget, PartialEq, Eq, Hash)]
+pub enum Enum1 {
+    A(u8),
+    B,
+}
+
+// The derived `Hash` hashes the discriminant as `u8`.
+#[into_flat(Enum2Flat)]
+#[derive(PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Enum2 {
+    #[flatten]
+    Enum1(Enum1),
+    C,
+}
+
+#[flat(Enum2, structured(PartialEq, Eq, Hash))]
+pub enum Enum2Flat {}
+
+fn main() {}
@@ -0,0 +1,117 @@
+use flat_enum::{flat, into_flat, FlatTarget};
+
+#[derive(FlatTarget)]
+#[flat_enum(visitor)]
+pub enum Literal<'a> {
+    Int(i64),
+    Str(&'a str),
+    Unit,
+}
+
+#[derive(FlatTarget)]
+#[flat_enum(visitor)]
+pub enum BinOp<A> {
+    Add { lhs: A, rhs: A },
+    Neg(A),
+}
+
+#[into_flat(ExprFlat<'a, A>)]
+pub enum Expr<'a, A> {
+    #[flatten]
+    Literal(Literal<'a>),
+    #[flatten]
+    BinOp(BinOp<A>),
+    Var(String),
+}
+
+#[flat(Expr<'a, A>, visitor)]
+#[derive(FlatTarget)]
+pub enum ExprFlat<'a, A> {}
+
+#[into_flat(StmtFlat<'a, A>)]
+pub enum Stmt<'a, A> {
+    #[flatten]
+    Expr(ExprFlat<'a, A>),
+    Return,
+}
+
+#[flat(Stmt<'a, A>)]
+#[derive(FlatTarget)]
+#[flat_enum(visitor)]
+pub enum StmtFlat<'a, A> {}
+
+#[derive(Default)]
+struct Printer(Vec<String>);
+
+impl<'a> LiteralVisitor<'a> for Printer {
+    fn visit_int(&mut self, a0: &i64) {
+        self.0.push(format!("int {}", a0));
+    }
+    fn visit_str(&mut self, a0: &&'a str) {
+        self.0.push(format!("str {}", a0));
+    }
+    fn visit_unit(&mut self) {
+        self.0.push("unit".to_owned());
+    }
+}
+
+impl BinOpVisitor<u8> for Printer {
+    fn visit_add(&mut self, lhs: &u8, rhs: &u8) {
+        self.0.push(format!("add {} {}", lhs, rhs));
+    }
+    fn visit_neg(&mut self, a0: &u8) {
+        self.0.push(format!("neg {}", a0));
+    }
+}
+
+impl<'a> ExprFlatVisitor<'a, u8> for Printer {
+    fn visit_var(&mut self, a0: &String) {
+        self.0.push(format!("var {}", a0));
+    }
+}
+
+impl<'a> StmtFlatVisitor<'a, u8> for Printer {
+    fn visit_return(&mut self) {
+        self.0.push("return".to_owned());
+    }
+}
+
+#[test]
+fn test_visitor() {
+    let mut printer = Printer::default();
+    Literal::Int(1).accept(&mut printer);
+    BinOp::Neg(2u8).accept(&mut printer);
+    assert_eq!(printer.0, ["int 1", "neg 2"]);
+
+    let mut printer = Printer::default();
+    let values: Vec<ExprFlat<u8>> = vec![
+        ExprFlat::Int(3),
+        ExprFlat::Str("abc"),
+        ExprFlat::Unit,
+        ExprFlat::Add { lhs: 1, rhs: 2 },
+        ExprFlat::Neg(4),
+        ExprFlat::Var("x".to_owned()),
+    ];
+    for value in &values {
+        value.accept(&mut printer);
+    }
+    assert_eq!(
+        printer.0,
+        ["int 3", "str abc", "unit", "add 1 2", "neg 4", "var x"]
+    );
+
+    let mut printer = Printer::default();
+    StmtFlat::<u8>::Str("def").accept(&mut printer);
+    StmtFlat::<u8>::Var("y".to_owned()).accept(&mut printer);
+    StmtFlat::<u8>::Return.accept(&mut printer);
+    assert_eq!(printer.0, ["str def", "var y", "return"]);
+}
+
+#[test]
+fn test_visitor_dyn() {
+    let mut printer = Printer::default();
+    let visitor: &mut dyn StmtFlatVisitor<u8> = &mut printer;
+    let value: StmtFlat<u8> = StmtFlat::Add { lhs: 5, rhs: 6 };
+    value.accept(visitor);
+    assert_eq!(printer.0, ["add 5 6"]);
+}